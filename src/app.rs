@@ -1,9 +1,29 @@
 //! Main application logic
 
-use crate::cli::{self, Config};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cli::{self, BinaryFiles, Config, OutputMode, SortBy};
+use crate::context::{ContextEvent, ContextLines};
 use crate::errors::{ExitCode, RgrepError};
-use crate::io::create_input_source;
-use crate::search::{create_matcher, format_match, search_lines};
+use crate::filter::PathFilter;
+use crate::io::{create_input_source, seek_stdin, InputSource};
+use crate::json;
+use crate::ordered::OrderedOutput;
+use crate::rewrite::{apply_rewrites, format_diff, plan_rewrite};
+use crate::search::{
+    create_matcher, format_binary_match, format_context, format_count, format_filename,
+    format_group_separator, format_match_with, format_only_matching, replace_matches, MatchResult,
+    Matcher, OutputOptions, SearchStats,
+};
+use crate::searcher::BufferSearcher;
+use crate::walk::Walk;
 
 /// Main entry point for the application
 pub fn run() -> ExitCode {
@@ -34,94 +54,521 @@ fn run_impl() -> Result<ExitCode, RgrepError> {
     execute_search(&config)
 }
 
+/// Label used in errors about writing the results
+const STDOUT_LABEL: &str = "(standard output)";
+
+/// Jobs each search thread may have started ahead of the file being printed
+const JOBS_PER_THREAD: usize = 4;
+
 /// Execute the search operation
+///
+/// With more than one thread and more than one input, files are searched
+/// concurrently but printed in input (or `--sort`) order, so lines from
+/// different files never interleave.
 pub fn execute_search(config: &Config) -> Result<ExitCode, RgrepError> {
-    let matcher = create_matcher(config);
-    let mut any_match = false;
+    let matcher = create_matcher(config)?;
+    let filter = PathFilter::new(config)?;
+    let mut stats = SearchStats::default();
 
     let files = config.actual_files();
-    let multiple_files = files.len() > 1;
+    let output_options = OutputOptions {
+        show_filename: files.len() > 1 || config.recursive,
+        show_line_numbers: config.line_number,
+        show_column: config.column,
+        show_byte_offset: config.byte_offset,
+        null: config.null,
+        color: config.color.should_colorize(),
+    };
 
-    if config.use_stdin() {
+    if config.in_place {
+        let paths = input_paths(&files, config, &filter);
+        return rewrite_in_place(paths, matcher.as_ref(), config);
+    }
+
+    let result = if config.use_stdin() {
         // Search stdin
         let input_source = create_input_source(None)?;
+        search_source(
+            input_source,
+            matcher.as_ref(),
+            &output_options,
+            config,
+            &mut io::stdout().lock(),
+        )
+        .map(|source_stats| stats = source_stats)
+    } else {
+        // Search files, expanding directories when searching recursively
+        let paths = input_paths(&files, config, &filter);
+        let paths = match config.sort {
+            SortBy::None => paths,
+            SortBy::Path => sort_paths(paths),
+        };
 
-        let matches = search_lines(input_source.lines, matcher.as_ref());
-        let matched_any = process_matches(
-            matches,
-            input_source.name.as_deref(),
-            false,
-            config.line_number,
-        );
+        let threads = config
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get));
 
-        if matched_any {
-            any_match = true;
+        if threads > 1 && (config.recursive || files.len() > 1) {
+            search_parallel(
+                paths,
+                matcher.as_ref(),
+                &output_options,
+                config,
+                threads,
+                &mut stats,
+            )
+        } else {
+            search_sequential(paths, matcher.as_ref(), &output_options, config, &mut stats)
         }
+    };
+
+    // The summary closes JSON output, after every input's events
+    let result = result.and_then(|()| match config.output_mode {
+        OutputMode::Json => write_line(
+            &mut io::stdout().lock(),
+            &json::format_summary(&stats),
+            b'\n',
+        ),
+        _ => Ok(()),
+    });
+
+    match result {
+        // The reader took what it wanted (e.g. `| head`), so stop quietly
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => return Ok(ExitCode::Success),
+        Err(error) => return Err(RgrepError::io_error(STDOUT_LABEL, error)),
+        Ok(()) => {}
+    }
+
+    if stats.matched_inputs > 0 {
+        Ok(ExitCode::Success)
     } else {
-        // Search files
-        for file_path in &files {
-            match process_file(file_path, &*matcher, multiple_files, config) {
-                Ok(matched) => {
-                    if matched {
-                        any_match = true;
-                    }
-                }
-                Err(error) => {
-                    // Print error but continue with other files
-                    eprintln!("{error}");
-                }
+        Ok(ExitCode::NoMatches)
+    }
+}
+
+/// Apply `--replace` to the files themselves
+///
+/// Every file is read and rewritten in memory before any is written, so an
+/// error on one file leaves all of them unchanged. With `--dry-run` the
+/// changes are printed as a unified diff instead.
+fn rewrite_in_place(
+    paths: impl Iterator<Item = Result<PathBuf, RgrepError>>,
+    matcher: &dyn Matcher,
+    config: &Config,
+) -> Result<ExitCode, RgrepError> {
+    let replacement = config.replace.as_deref().unwrap_or_default().as_bytes();
+    let mut rewrites = Vec::new();
+    let mut targets = HashSet::new();
+
+    for file_path in paths {
+        if let Some(rewrite) = plan_rewrite(&file_path?, matcher, replacement, config)? {
+            // A file named twice (`a.txt ./a.txt`, or through a symbolic
+            // link) is rewritten once
+            if targets.insert(rewrite.target().to_path_buf()) {
+                rewrites.push(rewrite);
             }
         }
     }
 
-    if any_match {
-        Ok(ExitCode::Success)
+    if config.dry_run {
+        let mut out = io::stdout().lock();
+        let written = rewrites
+            .iter()
+            .try_for_each(|rewrite| out.write_all(&format_diff(rewrite, config.line_terminator())))
+            .and_then(|()| out.flush());
+        match written {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(error) => return Err(RgrepError::io_error(STDOUT_LABEL, error)),
+            Ok(()) => {}
+        }
     } else {
+        apply_rewrites(&rewrites, config.backup)?;
+    }
+
+    if rewrites.is_empty() {
         Ok(ExitCode::NoMatches)
+    } else {
+        Ok(ExitCode::Success)
     }
 }
 
-/// Process a single file
-fn process_file(
-    file_path: &str,
-    matcher: &dyn crate::search::Matcher,
-    show_filename: bool,
+/// Search files one after another, printing results as they are found
+fn search_sequential(
+    paths: impl Iterator<Item = Result<PathBuf, RgrepError>>,
+    matcher: &dyn Matcher,
+    output_options: &OutputOptions,
     config: &Config,
-) -> Result<bool, RgrepError> {
-    let input_source = create_input_source(Some(file_path))?;
-
-    let matches = search_lines(input_source.lines, matcher);
-    let matched_any = process_matches(
-        matches,
-        input_source.name.as_deref(),
-        show_filename,
-        config.line_number,
+    stats: &mut SearchStats,
+) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+
+    for file_path in paths {
+        match file_path.and_then(InputSource::from_file) {
+            Ok(input_source) => {
+                *stats += search_source(input_source, matcher, output_options, config, &mut out)?;
+                if config.output_mode == OutputMode::Quiet && stats.matched_inputs > 0 {
+                    break;
+                }
+            }
+            Err(error) => {
+                // Print error but continue with other files
+                eprintln!("{error}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Search files on a pool of worker threads
+///
+/// The calling thread walks the inputs and hands out numbered jobs. The job
+/// first in order prints as it searches; the others buffer their output (see
+/// [`OrderedOutput`]) until their turn.
+fn search_parallel(
+    paths: impl Iterator<Item = Result<PathBuf, RgrepError>>,
+    matcher: &dyn Matcher,
+    output_options: &OutputOptions,
+    config: &Config,
+    threads: usize,
+    stats: &mut SearchStats,
+) -> io::Result<()> {
+    let output = OrderedOutput::new(
+        io::stdout(),
+        threads * JOBS_PER_THREAD,
+        config.output_mode == OutputMode::Quiet,
     );
+    let (job_tx, job_rx) = mpsc::channel::<(usize, Result<PathBuf, RgrepError>)>();
+    let job_rx = Mutex::new(job_rx);
 
-    Ok(matched_any)
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let (job_rx, output) = (&job_rx, &output);
+
+            scope.spawn(move || loop {
+                let job = job_rx.lock().expect("job queue poisoned").recv();
+                let Ok((index, file_path)) = job else {
+                    break;
+                };
+                if !output.wait_for_slot(index) {
+                    break;
+                }
+
+                let mut writer = output.writer(index);
+                let result = file_path
+                    .and_then(InputSource::from_file)
+                    .map(|input_source| {
+                        search_source(input_source, matcher, output_options, config, &mut writer)
+                    });
+                match result {
+                    Ok(Ok(file_stats)) => writer.finish(Ok(file_stats)),
+                    Err(error) => writer.finish(Err(error)),
+                    // Writing failed or the search stopped; the output
+                    // keeps the error
+                    Ok(Err(_)) => break,
+                }
+            });
+        }
+
+        for job in paths.enumerate() {
+            if output.is_stopped() || job_tx.send(job).is_err() {
+                break;
+            }
+        }
+
+        // Closing the queue lets the workers finish
+        drop(job_tx);
+    });
+
+    let (_, output_stats, result) = output.into_parts();
+    *stats += output_stats;
+    result
 }
 
-/// Process match results and print output
-fn process_matches<I>(
-    matches: I,
-    source_name: Option<&str>,
-    show_filename: bool,
-    show_line_numbers: bool,
-) -> bool
+/// Collect the inputs and sort them by path
+///
+/// Errors found while collecting keep their relative order and come first.
+fn sort_paths<'a>(
+    paths: impl Iterator<Item = Result<PathBuf, RgrepError>> + 'a,
+) -> Box<dyn Iterator<Item = Result<PathBuf, RgrepError>> + 'a> {
+    let mut paths: Vec<_> = paths.collect();
+    paths.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => a.cmp(b),
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => Ordering::Equal,
+    });
+    Box::new(paths.into_iter())
+}
+
+/// Expand the paths to search into the files to open
+///
+/// Files given that the filters reject are skipped; directories are not
+/// filtered themselves. Without `-r` the other paths are returned as given.
+/// With `-r`, directories are walked, skipping hidden, ignored and
+/// filtered-out entries unless asked otherwise; when no path was given,
+/// results are shown relative to `.`.
+fn input_paths<'a>(
+    files: &'a [String],
+    config: &'a Config,
+    filter: &'a PathFilter,
+) -> Box<dyn Iterator<Item = Result<PathBuf, RgrepError>> + 'a> {
+    let files = files.iter().filter(|file| {
+        let path = Path::new(file);
+        *file == "-" || path.is_dir() || filter.allows_operand(path)
+    });
+
+    if !config.recursive {
+        return Box::new(files.map(|file| Ok(PathBuf::from(file))));
+    }
+
+    let implicit_root = config.files.is_empty();
+
+    Box::new(files.flat_map(move |root| {
+        let walk = Walk::new(root, config.follow_links)
+            .respect_ignore(!config.no_ignore)
+            .hidden(config.hidden)
+            .filter(filter.clone());
+
+        walk.map(move |entry| {
+            entry.map(|path| match path.strip_prefix(".") {
+                Ok(relative) if implicit_root => relative.to_path_buf(),
+                _ => path,
+            })
+        })
+    }))
+}
+
+/// Search one input source, applying the binary file policy
+fn search_source<W: Write>(
+    input_source: InputSource,
+    matcher: &dyn Matcher,
+    output_options: &OutputOptions,
+    config: &Config,
+    out: &mut W,
+) -> io::Result<SearchStats> {
+    let InputSource {
+        name,
+        reader,
+        binary,
+        stdin_offset,
+    } = input_source;
+    let name = name.as_deref();
+    let searcher = BufferSearcher::new(reader, matcher)
+        .invert_match(config.invert_match)
+        .max_count(config.max_count)
+        .line_terminator(config.line_terminator());
+
+    // NUL bytes are expected as line terminators with `-z`
+    if binary && !config.null_data {
+        match config.binary_files {
+            BinaryFiles::WithoutMatch => {
+                return process_matches(std::iter::empty(), name, output_options, config, out);
+            }
+            BinaryFiles::Binary if config.output_mode == OutputMode::Lines => {
+                let matched_any = searcher
+                    .into_iter()
+                    .any(|m| m.is_selected(config.invert_match));
+                if matched_any {
+                    write_line(out, &format_binary_match(name), b'\n')?;
+                }
+                return Ok(SearchStats::for_input(usize::from(matched_any), 0));
+            }
+            _ => {}
+        }
+    }
+
+    let (before, after) = context_lines(config);
+    let mut searcher = searcher.context(before, after);
+    let matches = searcher.by_ref().map(|m| match &config.replace {
+        Some(replacement) => replace_matches(m, matcher, replacement.as_bytes()),
+        None => m,
+    });
+    let stats = process_matches(matches, name, output_options, config, out)?;
+
+    // Leave standard input just after the last selected line for the next
+    // reader; failing to do so does not affect our own results
+    if let (Some(start), Some(consumed)) = (stdin_offset, searcher.resume_offset()) {
+        let _ = seek_stdin(start + consumed as u64);
+    }
+
+    Ok(stats)
+}
+
+/// Process match results and print output according to the output mode
+///
+/// Returns the statistics of the input. In files-with(out)-match and quiet
+/// modes the iterator is only consumed up to the first selected line.
+fn process_matches<I, W>(
+    mut matches: I,
+    source_name: Option<&Path>,
+    output_options: &OutputOptions,
+    config: &Config,
+    out: &mut W,
+) -> io::Result<SearchStats>
 where
-    I: Iterator<Item = crate::search::MatchResult>,
+    I: Iterator<Item = MatchResult>,
+    W: Write,
 {
-    let mut any_match = false;
+    let is_selected = |m: &MatchResult| m.is_selected(config.invert_match);
 
-    for match_result in matches {
-        if match_result.matched {
-            any_match = true;
+    match config.output_mode {
+        OutputMode::Lines => print_lines(matches, source_name, output_options, config, out),
+        OutputMode::Json => print_json(matches, source_name, config, out),
+        OutputMode::Count => {
+            let (count, spans) = matches
+                .filter(is_selected)
+                .fold((0, 0), |(count, spans), m| {
+                    (count + 1, spans + m.spans.len())
+                });
+            // Counts are not data lines, so they end with a newline even with `-z`
+            let output = format_count(count, source_name, output_options);
+            write_line(out, &output, b'\n')?;
+            Ok(SearchStats::for_input(count, spans))
+        }
+        OutputMode::FilesWithMatches => {
+            let any_match = matches.any(|m| is_selected(&m));
+            if any_match {
+                write_line(
+                    out,
+                    &format_filename(source_name, output_options),
+                    filename_terminator(config),
+                )?;
+            }
+            Ok(SearchStats::for_input(usize::from(any_match), 0))
+        }
+        OutputMode::FilesWithoutMatch => {
+            let any_match = matches.any(|m| is_selected(&m));
+            if !any_match {
+                write_line(
+                    out,
+                    &format_filename(source_name, output_options),
+                    filename_terminator(config),
+                )?;
+            }
+            Ok(SearchStats::for_input(usize::from(any_match), 0))
+        }
+        OutputMode::Quiet => {
+            let any_match = matches.any(|m| is_selected(&m));
+            Ok(SearchStats::for_input(usize::from(any_match), 0))
+        }
+    }
+}
 
-            let output = format_match(&match_result, source_name, show_filename, show_line_numbers);
+/// Print selected lines along with any requested context lines
+fn print_lines<I, W>(
+    matches: I,
+    source_name: Option<&Path>,
+    output_options: &OutputOptions,
+    config: &Config,
+    out: &mut W,
+) -> io::Result<SearchStats>
+where
+    I: Iterator<Item = MatchResult>,
+    W: Write,
+{
+    let (mut selected_lines, mut spans) = (0, 0);
+    let (before, after) = context_lines(config);
+    let events = ContextLines::new(matches, config.invert_match, before, after);
 
-            println!("{output}");
+    for event in events {
+        if let ContextEvent::Selected(match_result) = &event {
+            selected_lines += 1;
+            spans += match_result.spans.len();
         }
+
+        let output = match event {
+            ContextEvent::Selected(match_result) if config.only_matching => {
+                for part in format_only_matching(&match_result, source_name, output_options) {
+                    write_line(out, &part, config.line_terminator())?;
+                }
+                continue;
+            }
+            ContextEvent::Selected(match_result) => {
+                format_match_with(&match_result, source_name, output_options)
+            }
+            ContextEvent::Context(match_result) => {
+                format_context(&match_result, source_name, output_options)
+            }
+            ContextEvent::GroupSeparator => format_group_separator(output_options),
+        };
+
+        write_line(out, &output, config.line_terminator())?;
     }
 
-    any_match
+    Ok(SearchStats::for_input(selected_lines, spans))
+}
+
+/// Print the JSON Lines events for one input
+///
+/// The input's events are framed by `begin` and `end` events; group
+/// separators are left out, as line numbers show where groups break. Events
+/// always end with a newline, even with `-z`.
+fn print_json<I, W>(
+    matches: I,
+    source_name: Option<&Path>,
+    config: &Config,
+    out: &mut W,
+) -> io::Result<SearchStats>
+where
+    I: Iterator<Item = MatchResult>,
+    W: Write,
+{
+    let (mut selected_lines, mut spans) = (0, 0);
+    let unit = config.column.unwrap_or_default();
+    let (before, after) = context_lines(config);
+    let events = ContextLines::new(matches, config.invert_match, before, after);
+
+    write_line(out, &json::format_begin(source_name), b'\n')?;
+
+    for event in events {
+        let output = match event {
+            ContextEvent::Selected(match_result) => {
+                selected_lines += 1;
+                spans += match_result.spans.len();
+                json::format_match(&match_result, source_name, unit)
+            }
+            ContextEvent::Context(match_result) => json::format_context(&match_result, source_name),
+            ContextEvent::GroupSeparator => continue,
+        };
+
+        write_line(out, &output, b'\n')?;
+    }
+
+    let stats = SearchStats::for_input(selected_lines, spans);
+    write_line(out, &json::format_end(source_name, &stats), b'\n')?;
+    Ok(stats)
+}
+
+/// Number of context lines to print before and after selected lines
+///
+/// Context only matters when whole lines are printed, so it is dropped for
+/// the other output modes and for `-o`.
+fn context_lines(config: &Config) -> (usize, usize) {
+    match config.output_mode {
+        OutputMode::Lines if !config.only_matching => (config.before_context, config.after_context),
+        OutputMode::Json => (config.before_context, config.after_context),
+        _ => (0, 0),
+    }
+}
+
+/// Terminator written after a file name printed on its own
+///
+/// With `-Z` it is NUL, so names containing newlines survive `xargs -0`;
+/// `-z` only changes how data lines end.
+fn filename_terminator(config: &Config) -> u8 {
+    if config.null {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+/// Write one output line as raw bytes, followed by `terminator`
+///
+/// Lines are written unchanged, so input that is not valid UTF-8 is
+/// reproduced exactly.
+fn write_line<W: Write>(out: &mut W, line: &[u8], terminator: u8) -> io::Result<()> {
+    out.write_all(line)?;
+    out.write_all(&[terminator])
 }
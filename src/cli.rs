@@ -1,20 +1,144 @@
 //! Command-line interface definition and parsing
 
-use clap::{error::ErrorKind as ClapErrorKind, CommandFactory, Parser};
+use clap::{error::ErrorKind as ClapErrorKind, ArgGroup, CommandFactory, Parser, ValueEnum};
 
+use crate::color::ColorChoice;
 use crate::errors::{ExitCode, RgrepError};
+use crate::io::read_patterns;
+use crate::types::TypeRegistry;
 
 /// Configuration for the search operation
 #[derive(Debug, Clone)]
 pub struct Config {
-    /// The search pattern (literal substring)
+    /// The search pattern (literal substring or regex)
     pub pattern: String,
+    /// Further patterns from `-e` and `-f`; a line is selected if any matches
+    pub patterns: Vec<String>,
+    /// The `-f` files gave no pattern at all, so no line matches and
+    /// `pattern` is unused
+    pub no_patterns: bool,
     /// Files to search (empty means stdin)
     pub files: Vec<String>,
     /// Case-insensitive matching
     pub ignore_case: bool,
+    /// Ignore case only if the pattern has no uppercase letters
+    pub smart_case: bool,
     /// Show line numbers in output
     pub line_number: bool,
+    /// Interpret the pattern as a regular expression
+    pub regex: bool,
+    /// When to colorize output
+    pub color: ColorChoice,
+    /// Only match whole words
+    pub word_regexp: bool,
+    /// Only match whole lines
+    pub line_regexp: bool,
+    /// Select lines that do not match the pattern
+    pub invert_match: bool,
+    /// What to print for each input
+    pub output_mode: OutputMode,
+    /// Print only the matched parts of selected lines
+    pub only_matching: bool,
+    /// Stop reading an input after this many selected lines
+    pub max_count: Option<usize>,
+    /// Print matches rewritten with this text, expanding capture references
+    pub replace: Option<String>,
+    /// Write the replacements back to the files searched
+    pub in_place: bool,
+    /// Keep a `.bak` copy of each file rewritten in place
+    pub backup: bool,
+    /// Print a diff of the in-place rewrite instead of doing it
+    pub dry_run: bool,
+    /// Follow printed file names with NUL instead of a separator or newline
+    pub null: bool,
+    /// Lines in input and output end with NUL instead of `\n`
+    pub null_data: bool,
+    /// Print the column of the first match, counted in this unit
+    pub column: Option<ColumnUnit>,
+    /// Print the byte offset of each line (or match with `-o`) in its input
+    pub byte_offset: bool,
+    /// Number of context lines to print before each selected line
+    pub before_context: usize,
+    /// Number of context lines to print after each selected line
+    pub after_context: usize,
+    /// Search directories recursively
+    pub recursive: bool,
+    /// Follow symbolic links found while searching recursively
+    pub follow_links: bool,
+    /// Do not honour ignore files while searching recursively
+    pub no_ignore: bool,
+    /// Search hidden files and directories while searching recursively
+    pub hidden: bool,
+    /// Only search files matching one of these globs
+    pub include: Vec<String>,
+    /// Skip files matching any of these globs
+    pub exclude: Vec<String>,
+    /// Skip directories matching any of these globs
+    pub exclude_dir: Vec<String>,
+    /// Include (`glob`) or exclude (`!glob`) overrides, later ones win
+    pub globs: Vec<String>,
+    /// Only search files of these named types
+    pub types: Vec<String>,
+    /// Skip files of these named types
+    pub types_not: Vec<String>,
+    /// Additional `name:glob` file type definitions
+    pub type_add: Vec<String>,
+    /// How to handle files that look binary
+    pub binary_files: BinaryFiles,
+    /// Number of files to search concurrently (None uses every core)
+    pub threads: Option<usize>,
+    /// Order in which results for different files are printed
+    pub sort: SortBy,
+}
+
+/// How to handle inputs detected as binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum BinaryFiles {
+    /// Report "Binary file X matches" instead of printing lines
+    #[default]
+    Binary,
+    /// Search and print binary files as if they were text
+    Text,
+    /// Treat binary files as not matching
+    WithoutMatch,
+}
+
+/// Order in which per-file results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SortBy {
+    /// The order in which files are given or discovered
+    #[default]
+    None,
+    /// Sort files by path
+    Path,
+}
+
+/// Unit in which `--column` counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColumnUnit {
+    /// Count characters, so columns match what an editor shows
+    #[default]
+    Chars,
+    /// Count bytes
+    Bytes,
+}
+
+/// What to print for each searched input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Print every selected line
+    #[default]
+    Lines,
+    /// Print the number of selected lines
+    Count,
+    /// Print the name of inputs with at least one selected line
+    FilesWithMatches,
+    /// Print the name of inputs without any selected line
+    FilesWithoutMatch,
+    /// Print every selected and context line as a JSON Lines event
+    Json,
+    /// Print nothing and stop at the first selected line
+    Quiet,
 }
 
 /// Actions that the CLI can perform
@@ -24,8 +148,10 @@ pub enum CliAction {
     ShowHelp,
     /// Show version and exit
     ShowVersion,
+    /// Show the known file types and exit
+    ShowTypeList(TypeRegistry),
     /// Run the search with given configuration
-    Run(Config),
+    Run(Box<Config>),
 }
 
 /// CLI argument definition using Clap derive
@@ -35,20 +161,400 @@ pub enum CliAction {
     version = "0.1.0",
     about = "A simple grep-like tool written in Rust",
     long_about = "rgrep searches for patterns in files or standard input.\n\
-                  It supports case-insensitive matching and line number display."
+                  It supports literal and regular expression patterns, case-insensitive\n\
+                  matching and line number display."
 )]
+#[command(group(
+    ArgGroup::new("output_mode").args(["count", "files_with_matches", "files_without_match", "json"])
+))]
 struct Cli {
     /// Case-insensitive matching
-    #[arg(short = 'i', long = "ignore-case", help = "Ignore case when matching")]
+    #[arg(
+        short = 'i',
+        long = "ignore-case",
+        overrides_with_all = ["case_sensitive", "smart_case"],
+        help = "Ignore case when matching"
+    )]
     ignore_case: bool,
 
+    /// Case-sensitive matching
+    #[arg(
+        short = 's',
+        long = "case-sensitive",
+        overrides_with_all = ["ignore_case", "smart_case"],
+        help = "Match case exactly (default)"
+    )]
+    case_sensitive: bool,
+
+    /// Smart case matching
+    #[arg(
+        short = 'S',
+        long = "smart-case",
+        overrides_with_all = ["ignore_case", "case_sensitive"],
+        help = "Ignore case unless the pattern contains an uppercase letter"
+    )]
+    smart_case: bool,
+
     /// Show line numbers
     #[arg(short = 'n', long = "line-number", help = "Show line numbers")]
     line_number: bool,
 
-    /// The search pattern (literal substring)
-    #[arg(help = "Pattern to search for", required = true)]
-    pattern: String,
+    /// Select non-matching lines
+    #[arg(short = 'v', long = "invert-match", help = "Select non-matching lines")]
+    invert_match: bool,
+
+    /// Match whole words only
+    #[arg(
+        short = 'w',
+        long = "word-regexp",
+        help = "Only match whole words, bounded by non-word characters"
+    )]
+    word_regexp: bool,
+
+    /// Match whole lines only
+    #[arg(
+        short = 'x',
+        long = "line-regexp",
+        help = "Only match whole lines (takes precedence over -w)"
+    )]
+    line_regexp: bool,
+
+    /// Print only the matched parts of lines
+    #[arg(
+        short = 'o',
+        long = "only-matching",
+        help = "Print only the matched parts of lines, each on its own line"
+    )]
+    only_matching: bool,
+
+    /// Print the column of the first match
+    #[arg(
+        long = "column",
+        value_name = "UNIT",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "chars",
+        help = "Show the 1-based column of the first match in each line"
+    )]
+    column: Option<ColumnUnit>,
+
+    /// Print byte offsets
+    #[arg(
+        short = 'b',
+        long = "byte-offset",
+        help = "Show the byte offset of each line (or match with -o) in its file"
+    )]
+    byte_offset: bool,
+
+    /// Print only a count of selected lines per input
+    #[arg(
+        short = 'c',
+        long = "count",
+        help = "Print only a count of selected lines per file"
+    )]
+    count: bool,
+
+    /// Print only names of inputs with selected lines
+    #[arg(
+        short = 'l',
+        long = "files-with-matches",
+        help = "Print only names of files with selected lines"
+    )]
+    files_with_matches: bool,
+
+    /// Print only names of inputs without selected lines
+    #[arg(
+        short = 'L',
+        long = "files-without-match",
+        help = "Print only names of files without selected lines"
+    )]
+    files_without_match: bool,
+
+    /// Print nothing, exiting successfully at the first selected line
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        visible_alias = "silent",
+        help = "Print nothing; exit 0 as soon as any line is selected"
+    )]
+    quiet: bool,
+
+    /// Stop after NUM selected lines per input
+    #[arg(
+        short = 'm',
+        long = "max-count",
+        value_name = "NUM",
+        value_parser = clap::value_parser!(usize),
+        help = "Stop reading each file after NUM selected lines"
+    )]
+    max_count: Option<usize>,
+
+    /// Replacement for matched text in the output
+    #[arg(
+        long = "replace",
+        value_name = "TEXT",
+        allow_hyphen_values = true,
+        help = "Print each match replaced by TEXT ($1 or ${name} with -E); see --in-place"
+    )]
+    replace: Option<String>,
+
+    /// Write replacements back to the files
+    #[arg(
+        long = "in-place",
+        requires = "replace",
+        conflicts_with_all = ["output_mode", "quiet"],
+        help = "Rewrite files with --replace applied instead of printing lines"
+    )]
+    in_place: bool,
+
+    /// Keep a copy of each rewritten file
+    #[arg(
+        long = "backup",
+        requires = "in_place",
+        help = "Keep the original of each rewritten file as FILE.bak"
+    )]
+    backup: bool,
+
+    /// Show the rewrite without doing it
+    #[arg(
+        long = "dry-run",
+        requires = "in_place",
+        help = "Print a unified diff of the rewrite; leave files unchanged"
+    )]
+    dry_run: bool,
+
+    /// Terminate file names with NUL
+    #[arg(
+        short = 'Z',
+        long = "null",
+        help = "Print a NUL byte after file names instead of ':' or a newline"
+    )]
+    null: bool,
+
+    /// Use NUL as the line terminator
+    #[arg(
+        short = 'z',
+        long = "null-data",
+        help = "Lines in input and output end with a NUL byte, not a newline"
+    )]
+    null_data: bool,
+
+    /// Print results as JSON Lines
+    #[arg(
+        long = "json",
+        help = "Print one JSON object per file, line and summary (JSON Lines)"
+    )]
+    json: bool,
+
+    /// Lines of trailing context
+    #[arg(
+        short = 'A',
+        long = "after-context",
+        value_name = "NUM",
+        help = "Print NUM lines of trailing context"
+    )]
+    after_context: Option<usize>,
+
+    /// Lines of leading context
+    #[arg(
+        short = 'B',
+        long = "before-context",
+        value_name = "NUM",
+        help = "Print NUM lines of leading context"
+    )]
+    before_context: Option<usize>,
+
+    /// Lines of leading and trailing context
+    #[arg(
+        short = 'C',
+        long = "context",
+        value_name = "NUM",
+        help = "Print NUM lines of leading and trailing context"
+    )]
+    context: Option<usize>,
+
+    /// Search directories recursively
+    #[arg(
+        short = 'r',
+        long = "recursive",
+        help = "Search directories recursively (default: current directory)"
+    )]
+    recursive: bool,
+
+    /// Search directories recursively, following symbolic links
+    #[arg(
+        short = 'R',
+        long = "dereference-recursive",
+        help = "Like -r, but follow all symbolic links"
+    )]
+    dereference_recursive: bool,
+
+    /// Don't respect ignore files
+    #[arg(
+        long = "no-ignore",
+        help = "Don't respect .gitignore, .ignore and .rgrepignore files"
+    )]
+    no_ignore: bool,
+
+    /// Search hidden files and directories
+    #[arg(long = "hidden", help = "Search hidden files and directories")]
+    hidden: bool,
+
+    /// Only search files matching GLOB
+    #[arg(
+        long = "include",
+        value_name = "GLOB",
+        help = "Search only files whose name matches GLOB"
+    )]
+    include: Vec<String>,
+
+    /// Skip files matching GLOB
+    #[arg(
+        long = "exclude",
+        value_name = "GLOB",
+        help = "Skip files whose name matches GLOB"
+    )]
+    exclude: Vec<String>,
+
+    /// Skip directories matching GLOB
+    #[arg(
+        long = "exclude-dir",
+        value_name = "GLOB",
+        help = "Skip directories whose name matches GLOB"
+    )]
+    exclude_dir: Vec<String>,
+
+    /// Include or exclude files matching GLOB
+    #[arg(
+        short = 'g',
+        long = "glob",
+        value_name = "GLOB",
+        allow_hyphen_values = true,
+        help = "Include files matching GLOB, or exclude them with '!GLOB'"
+    )]
+    globs: Vec<String>,
+
+    /// Only search files of TYPE
+    #[arg(
+        short = 't',
+        long = "type",
+        value_name = "TYPE",
+        help = "Search only files of TYPE (see --type-list)"
+    )]
+    types: Vec<String>,
+
+    /// Skip files of TYPE
+    #[arg(
+        short = 'T',
+        long = "type-not",
+        value_name = "TYPE",
+        help = "Skip files of TYPE"
+    )]
+    types_not: Vec<String>,
+
+    /// Add a file type definition
+    #[arg(
+        long = "type-add",
+        value_name = "NAME:GLOB",
+        help = "Add GLOB to file type NAME"
+    )]
+    type_add: Vec<String>,
+
+    /// List the known file types
+    #[arg(long = "type-list", help = "Show all known file types and exit")]
+    type_list: bool,
+
+    /// How to handle binary files
+    #[arg(
+        long = "binary-files",
+        value_name = "TYPE",
+        value_enum,
+        default_value_t = BinaryFiles::Binary,
+        help = "How to handle binary files"
+    )]
+    binary_files: BinaryFiles,
+
+    /// Process binary files as text
+    #[arg(
+        short = 'a',
+        long = "text",
+        help = "Process binary files as text (--binary-files=text)"
+    )]
+    text: bool,
+
+    /// Skip binary files
+    #[arg(
+        short = 'I',
+        help = "Treat binary files as non-matching (--binary-files=without-match)"
+    )]
+    skip_binary: bool,
+
+    /// Number of worker threads
+    #[arg(
+        short = 'j',
+        long = "threads",
+        value_name = "NUM",
+        help = "Search NUM files at once (default: number of CPU cores)"
+    )]
+    threads: Option<usize>,
+
+    /// Output order
+    #[arg(
+        long = "sort",
+        value_name = "BY",
+        value_enum,
+        default_value_t = SortBy::None,
+        help = "Print results for each file in this order"
+    )]
+    sort: SortBy,
+
+    /// Additional patterns
+    #[arg(
+        short = 'e',
+        long = "pattern",
+        value_name = "PATTERN",
+        allow_hyphen_values = true,
+        help = "Search for PATTERN; may be given more than once"
+    )]
+    patterns: Vec<String>,
+
+    /// Files of patterns
+    #[arg(
+        short = 'f',
+        long = "file",
+        value_name = "FILE",
+        help = "Read patterns from FILE, one per line ('-' for stdin)"
+    )]
+    pattern_files: Vec<String>,
+
+    /// Interpret pattern as a regular expression
+    #[arg(
+        short = 'E',
+        long = "regexp",
+        help = "Interpret pattern as a regular expression"
+    )]
+    regex: bool,
+
+    /// When to use colors
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto",
+        help = "Highlight matches, file names and line numbers"
+    )]
+    color: ColorChoice,
+
+    /// The search pattern
+    #[arg(
+        help = "Pattern to search for (a file to search if -e or -f is given)",
+        required_unless_present_any = ["type_list", "patterns", "pattern_files"]
+    )]
+    pattern: Option<String>,
 
     /// Files to search (use '-' for stdin)
     #[arg(help = "Files to search (default: stdin)")]
@@ -60,15 +566,50 @@ impl Config {
     pub fn new(pattern: String, files: Vec<String>, ignore_case: bool, line_number: bool) -> Self {
         Self {
             pattern,
+            patterns: Vec::new(),
+            no_patterns: false,
             files,
             ignore_case,
+            smart_case: false,
             line_number,
+            regex: false,
+            color: ColorChoice::Auto,
+            word_regexp: false,
+            line_regexp: false,
+            invert_match: false,
+            output_mode: OutputMode::Lines,
+            only_matching: false,
+            max_count: None,
+            replace: None,
+            in_place: false,
+            backup: false,
+            dry_run: false,
+            null: false,
+            null_data: false,
+            column: None,
+            byte_offset: false,
+            before_context: 0,
+            after_context: 0,
+            recursive: false,
+            follow_links: false,
+            no_ignore: false,
+            hidden: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            exclude_dir: Vec::new(),
+            globs: Vec::new(),
+            types: Vec::new(),
+            types_not: Vec::new(),
+            type_add: Vec::new(),
+            binary_files: BinaryFiles::Binary,
+            threads: None,
+            sort: SortBy::None,
         }
     }
 
     /// Validate the configuration
     pub fn validate(&self) -> Result<(), RgrepError> {
-        if self.pattern.is_empty() {
+        if self.all_patterns().any(str::is_empty) {
             return Err(RgrepError::invalid_args("Pattern cannot be empty", false));
         }
 
@@ -80,18 +621,61 @@ impl Config {
             ));
         }
 
+        if self.in_place && self.use_stdin() {
+            return Err(RgrepError::invalid_args(
+                "Cannot rewrite standard input in place",
+                false,
+            ));
+        }
+
+        if self.in_place && self.replace.is_none() {
+            return Err(RgrepError::invalid_args(
+                "--in-place requires --replace",
+                false,
+            ));
+        }
+
+        if self.threads == Some(0) {
+            return Err(RgrepError::invalid_args(
+                "Number of threads must be at least 1",
+                false,
+            ));
+        }
+
         Ok(())
     }
 
+    /// All patterns to search for
+    pub fn all_patterns(&self) -> impl Iterator<Item = &str> {
+        let first = (!self.no_patterns).then_some(self.pattern.as_str());
+        first
+            .into_iter()
+            .chain(self.patterns.iter().map(String::as_str))
+    }
+
+    /// Byte ending each line of input and output
+    pub fn line_terminator(&self) -> u8 {
+        if self.null_data {
+            b'\0'
+        } else {
+            b'\n'
+        }
+    }
+
     /// Check if we should read from stdin
     pub fn use_stdin(&self) -> bool {
-        self.files.is_empty() || (self.files.len() == 1 && self.files[0] == "-")
+        (self.files.is_empty() && !self.recursive)
+            || (self.files.len() == 1 && self.files[0] == "-")
     }
 
     /// Get the actual files to process (filters out stdin indicators)
+    ///
+    /// A recursive search without paths searches the current directory.
     pub fn actual_files(&self) -> Vec<String> {
         if self.use_stdin() {
             Vec::new()
+        } else if self.files.is_empty() {
+            vec![".".to_string()]
         } else {
             self.files.clone()
         }
@@ -104,31 +688,137 @@ where
     I: IntoIterator<Item = String>,
 {
     // Clap expects argv[0] as the binary name
-    let argv: Vec<String> = std::iter::once("rgrep".to_string())
-        .chain(args)
-        .collect();
+    let argv: Vec<String> = std::iter::once("rgrep".to_string()).chain(args).collect();
 
     let cmd = build_command();
 
     match cmd.try_get_matches_from(argv) {
         Ok(matches) => {
+            if matches.get_flag("type_list") {
+                let registry = TypeRegistry::with_additions(&string_values(&matches, "type_add"))?;
+                return Ok(CliAction::ShowTypeList(registry));
+            }
+
             let ignore_case = matches.get_flag("ignore_case");
             let line_number = matches.get_flag("line_number");
+            let regex = matches.get_flag("regex");
+            let invert_match = matches.get_flag("invert_match");
+
+            let output_mode = if matches.get_flag("quiet") {
+                OutputMode::Quiet
+            } else if matches.get_flag("count") {
+                OutputMode::Count
+            } else if matches.get_flag("files_with_matches") {
+                OutputMode::FilesWithMatches
+            } else if matches.get_flag("files_without_match") {
+                OutputMode::FilesWithoutMatch
+            } else if matches.get_flag("json") {
+                OutputMode::Json
+            } else {
+                OutputMode::Lines
+            };
+            let color = *matches
+                .get_one::<ColorChoice>("color")
+                .expect("color has a default");
 
-            let pattern = matches
-                .get_one::<String>("pattern")
-                .expect("pattern is required")
-                .to_string();
+            let mut positional = matches.get_one::<String>("pattern").cloned();
+            let mut files = string_values(&matches, "files");
 
-            let files: Vec<String> = matches
-                .get_many::<String>("files")
-                .map(|vals| vals.map(|s| s.to_string()).collect())
-                .unwrap_or_default();
+            // With -e or -f, the first positional argument is a file
+            let pattern_files = string_values(&matches, "pattern_files");
+            let explicit_patterns =
+                matches.get_many::<String>("patterns").is_some() || !pattern_files.is_empty();
+            if explicit_patterns {
+                if let Some(path) = positional.take() {
+                    files.insert(0, path);
+                }
+            }
 
-            let config = Config::new(pattern, files, ignore_case, line_number);
+            // -A and -B take precedence over -C for their side
+            let context = matches.get_one::<usize>("context").copied().unwrap_or(0);
+            let before_context = matches
+                .get_one::<usize>("before_context")
+                .copied()
+                .unwrap_or(context);
+            let after_context = matches
+                .get_one::<usize>("after_context")
+                .copied()
+                .unwrap_or(context);
+
+            let follow_links = matches.get_flag("dereference_recursive");
+            let recursive = matches.get_flag("recursive") || follow_links;
+
+            let mut config = Config::new(
+                positional.unwrap_or_default(),
+                files,
+                ignore_case,
+                line_number,
+            );
+            config.smart_case = matches.get_flag("smart_case");
+            config.regex = regex;
+            config.color = color;
+            config.word_regexp = matches.get_flag("word_regexp");
+            config.line_regexp = matches.get_flag("line_regexp");
+            config.invert_match = invert_match;
+            config.only_matching = matches.get_flag("only_matching");
+            config.max_count = matches.get_one::<usize>("max_count").copied();
+            config.replace = matches.get_one::<String>("replace").cloned();
+            config.in_place = matches.get_flag("in_place");
+            config.backup = matches.get_flag("backup");
+            config.dry_run = matches.get_flag("dry_run");
+            config.null = matches.get_flag("null");
+            config.null_data = matches.get_flag("null_data");
+            config.column = matches.get_one::<ColumnUnit>("column").copied();
+            config.byte_offset = matches.get_flag("byte_offset");
+            config.output_mode = output_mode;
+            config.before_context = before_context;
+            config.after_context = after_context;
+            config.recursive = recursive;
+            config.follow_links = follow_links;
+            config.no_ignore = matches.get_flag("no_ignore");
+            config.hidden = matches.get_flag("hidden");
+            config.include = string_values(&matches, "include");
+            config.exclude = string_values(&matches, "exclude");
+            config.exclude_dir = string_values(&matches, "exclude_dir");
+            config.globs = string_values(&matches, "globs");
+            config.types = string_values(&matches, "types");
+            config.types_not = string_values(&matches, "types_not");
+            config.type_add = string_values(&matches, "type_add");
+            config.binary_files = if matches.get_flag("text") {
+                BinaryFiles::Text
+            } else if matches.get_flag("skip_binary") {
+                BinaryFiles::WithoutMatch
+            } else {
+                *matches
+                    .get_one::<BinaryFiles>("binary_files")
+                    .expect("binary-files has a default")
+            };
+            config.threads = matches.get_one::<usize>("threads").copied();
+            config.sort = *matches
+                .get_one::<SortBy>("sort")
+                .expect("sort has a default");
+
+            if explicit_patterns {
+                if config.use_stdin() && pattern_files.iter().any(|path| path == "-") {
+                    return Err(RgrepError::invalid_args(
+                        "Cannot read both patterns and input from stdin",
+                        false,
+                    ));
+                }
+
+                // Only empty `-f` files give no patterns, as with grep
+                let mut patterns = collect_patterns(&matches)?.into_iter();
+                match patterns.next() {
+                    Some(first) => {
+                        config.pattern = first;
+                        config.patterns = patterns.collect();
+                    }
+                    None => config.no_patterns = true,
+                }
+            }
             config.validate()?;
 
-            Ok(CliAction::Run(config))
+            Ok(CliAction::Run(Box::new(config)))
         }
         Err(e) => match e.kind() {
             ClapErrorKind::DisplayHelp => Ok(CliAction::ShowHelp),
@@ -138,6 +828,38 @@ where
     }
 }
 
+/// Collect the `-e` patterns and the contents of `-f` files in the order given
+fn collect_patterns(matches: &clap::ArgMatches) -> Result<Vec<String>, RgrepError> {
+    let mut sources = Vec::new();
+
+    if let (Some(indices), Some(values)) = (
+        matches.indices_of("patterns"),
+        matches.get_many::<String>("patterns"),
+    ) {
+        sources.extend(indices.zip(values).map(|(idx, p)| (idx, vec![p.clone()])));
+    }
+
+    if let (Some(indices), Some(values)) = (
+        matches.indices_of("pattern_files"),
+        matches.get_many::<String>("pattern_files"),
+    ) {
+        for (idx, path) in indices.zip(values) {
+            sources.push((idx, read_patterns(path)?));
+        }
+    }
+
+    sources.sort_by_key(|&(idx, _)| idx);
+    Ok(sources.into_iter().flat_map(|(_, p)| p).collect())
+}
+
+/// Collect the values of a repeatable string argument
+fn string_values(matches: &clap::ArgMatches, id: &str) -> Vec<String> {
+    matches
+        .get_many::<String>(id)
+        .map(|vals| vals.map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
 /// Build the Clap command with additional configuration
 fn build_command() -> clap::Command {
     let mut cmd = Cli::command();
@@ -145,7 +867,27 @@ fn build_command() -> clap::Command {
         "Examples:\n  \
          rgrep foo file.txt              Search for 'foo' in file.txt\n  \
          rgrep -i error *.log            Case-insensitive search in log files\n  \
+         rgrep -S todo src/main.rs       Ignore case unless the pattern has capitals\n  \
          rgrep -n pattern file1 file2    Show line numbers for matches\n  \
+         rgrep -E 'err[0-9]+' app.log    Search with a regular expression\n  \
+         rgrep -e foo -e bar app.log     Search for either 'foo' or 'bar'\n  \
+         rgrep -f blocklist.txt -r .     Search for every pattern in a file\n  \
+         rgrep --color=always foo f.txt  Highlight matches even when piped\n  \
+         rgrep -v '#' config.ini         Show lines without comments\n  \
+         rgrep -w id src/*.rs            Find 'id' but not 'width' or 'valid'\n  \
+         rgrep --replace bar foo f.txt   Preview replacing 'foo' with 'bar'\n  \
+         rgrep -oE 'req-[0-9]+' app.log  Print only the matched request IDs\n  \
+         rgrep -n --column todo f.rs     Show line and column of each match\n  \
+         rgrep -l TODO src/*.rs          List files containing 'TODO'\n  \
+         rgrep -rlZ TODO . | xargs -0 wc List matching files for xargs -0\n  \
+         rgrep --json -r unwrap src      Report matches as JSON Lines\n  \
+         rgrep -C 2 panic app.log        Show 2 lines around each match\n  \
+         rgrep -m 1 ERROR app.log        Stop at the first matching line\n  \
+         rgrep -q TODO f.rs && echo yes  Only test whether anything matches\n  \
+         rgrep -r fixme src              Search every file under src/\n  \
+         rgrep -r -g '!*.lock' serde     Skip lock files while searching\n  \
+         rgrep -r -t rust unwrap         Search only Rust source files\n  \
+         rgrep -r --sort path -j 4 todo  Search with 4 threads, sorted by path\n  \
          echo 'test' | rgrep test        Search in stdin\n  \
          rgrep pattern -                 Explicitly search stdin",
     );
@@ -176,6 +918,10 @@ pub fn handle_action(action: CliAction) -> Result<Config, ExitCode> {
             println!("{}", version());
             Err(ExitCode::Success)
         }
-        CliAction::Run(config) => Ok(config),
+        CliAction::ShowTypeList(registry) => {
+            println!("{}", registry.list());
+            Err(ExitCode::Success)
+        }
+        CliAction::Run(config) => Ok(*config),
     }
 }
@@ -0,0 +1,55 @@
+//! ANSI color support for terminal output
+
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+
+/// Style for matched text (bold red)
+pub const MATCH_STYLE: &str = "\x1b[1;31m";
+/// Style for file names (magenta)
+pub const FILENAME_STYLE: &str = "\x1b[35m";
+/// Style for line numbers (green)
+pub const LINE_NUMBER_STYLE: &str = "\x1b[32m";
+/// Style for separators between prefix fields (cyan)
+pub const SEPARATOR_STYLE: &str = "\x1b[36m";
+/// Sequence that resets all styles
+pub const RESET: &str = "\x1b[0m";
+
+/// When to colorize output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Colorize when stdout is a terminal and NO_COLOR is unset
+    #[default]
+    Auto,
+    /// Always colorize
+    Always,
+    /// Never colorize
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve the choice against the current environment
+    pub fn should_colorize(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => no_color_unset() && std::io::stdout().is_terminal(),
+        }
+    }
+}
+
+/// Check the NO_COLOR convention (https://no-color.org)
+fn no_color_unset() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Append `text` to `output`, wrapped in `style` when coloring is enabled
+pub fn push_styled(output: &mut Vec<u8>, text: &[u8], style: &str, color: bool) {
+    if color {
+        output.extend_from_slice(style.as_bytes());
+        output.extend_from_slice(text);
+        output.extend_from_slice(RESET.as_bytes());
+    } else {
+        output.extend_from_slice(text);
+    }
+}
@@ -0,0 +1,159 @@
+//! Context line handling (`-A`, `-B`, `-C`)
+
+use std::collections::VecDeque;
+
+use crate::search::MatchResult;
+
+/// A line or marker produced by the context pipeline
+#[derive(Debug)]
+pub enum ContextEvent {
+    /// A selected line
+    Selected(MatchResult),
+    /// A line printed only as context around a selected line
+    Context(MatchResult),
+    /// Marker between groups of lines that are not adjacent
+    GroupSeparator,
+}
+
+/// Iterator adapter that surrounds selected lines with context lines
+///
+/// Preceding lines are kept in a ring buffer bounded by `before`, so memory
+/// use does not depend on the input size. Overlapping windows are merged and
+/// each line is emitted at most once.
+pub struct ContextLines<I> {
+    inner: I,
+    invert_match: bool,
+    before: usize,
+    after: usize,
+    buffer: VecDeque<MatchResult>,
+    pending: VecDeque<ContextEvent>,
+    after_remaining: usize,
+    last_emitted: Option<usize>,
+}
+
+impl<I> ContextLines<I>
+where
+    I: Iterator<Item = MatchResult>,
+{
+    /// Wrap a match iterator, keeping `before` and `after` lines of context
+    pub fn new(inner: I, invert_match: bool, before: usize, after: usize) -> Self {
+        Self {
+            inner,
+            invert_match,
+            before,
+            after,
+            buffer: VecDeque::with_capacity(before),
+            pending: VecDeque::new(),
+            after_remaining: 0,
+            last_emitted: None,
+        }
+    }
+
+    /// Queue a line, preceded by a separator if it does not follow the last one
+    fn emit(&mut self, match_result: MatchResult, selected: bool) {
+        let has_context = self.before > 0 || self.after > 0;
+
+        if let (true, Some(last)) = (has_context, self.last_emitted) {
+            if match_result.line_number > last + 1 {
+                self.pending.push_back(ContextEvent::GroupSeparator);
+            }
+        }
+
+        self.last_emitted = Some(match_result.line_number);
+        self.pending.push_back(if selected {
+            ContextEvent::Selected(match_result)
+        } else {
+            ContextEvent::Context(match_result)
+        });
+    }
+}
+
+impl<I> Iterator for ContextLines<I>
+where
+    I: Iterator<Item = MatchResult>,
+{
+    type Item = ContextEvent;
+
+    fn next(&mut self) -> Option<ContextEvent> {
+        while self.pending.is_empty() {
+            let match_result = self.inner.next()?;
+
+            if match_result.is_selected(self.invert_match) {
+                while let Some(previous) = self.buffer.pop_front() {
+                    self.emit(previous, false);
+                }
+                self.emit(match_result, true);
+                self.after_remaining = self.after;
+            } else if self.after_remaining > 0 {
+                self.after_remaining -= 1;
+                self.emit(match_result, false);
+            } else if self.before > 0 {
+                if self.buffer.len() == self.before {
+                    self.buffer.pop_front();
+                }
+                self.buffer.push_back(match_result);
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(matching: &[usize], total: usize) -> impl Iterator<Item = MatchResult> {
+        let matching = matching.to_vec();
+        (1..=total).map(move |line_number| MatchResult {
+            line: format!("line {line_number}").into_bytes(),
+            line_number,
+            byte_offset: 0,
+            matched: matching.contains(&line_number),
+            spans: Vec::new(),
+        })
+    }
+
+    fn render(events: impl Iterator<Item = ContextEvent>) -> Vec<String> {
+        events
+            .map(|event| match event {
+                ContextEvent::Selected(m) => format!("{}:", m.line_number),
+                ContextEvent::Context(m) => format!("{}-", m.line_number),
+                ContextEvent::GroupSeparator => "--".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_before_and_after_context() {
+        let events = ContextLines::new(lines(&[5], 10), false, 2, 1);
+        assert_eq!(render(events), vec!["3-", "4-", "5:", "6-"]);
+    }
+
+    #[test]
+    fn test_overlapping_windows_are_merged() {
+        let events = ContextLines::new(lines(&[3, 5], 10), false, 1, 1);
+        assert_eq!(render(events), vec!["2-", "3:", "4-", "5:", "6-"]);
+    }
+
+    #[test]
+    fn test_separator_between_distant_groups() {
+        let events = ContextLines::new(lines(&[2, 9], 10), false, 1, 1);
+        assert_eq!(
+            render(events),
+            vec!["1-", "2:", "3-", "--", "8-", "9:", "10-"]
+        );
+    }
+
+    #[test]
+    fn test_no_separator_without_context() {
+        let events = ContextLines::new(lines(&[2, 9], 10), false, 0, 0);
+        assert_eq!(render(events), vec!["2:", "9:"]);
+    }
+
+    #[test]
+    fn test_context_with_inverted_selection() {
+        let events = ContextLines::new(lines(&[1, 2, 4, 5], 5), true, 1, 0);
+        assert_eq!(render(events), vec!["2-", "3:"]);
+    }
+}
@@ -0,0 +1,232 @@
+//! Include/exclude glob filters for the files searched
+
+use std::path::Path;
+
+use crate::cli::Config;
+use crate::errors::RgrepError;
+use crate::glob::Glob;
+use crate::types::TypeRegistry;
+use crate::walk::slash_path;
+
+/// A glob applied to a path relative to the search root
+///
+/// Patterns without a `/` match the file name at any depth, patterns with a
+/// `/` match the whole relative path.
+#[derive(Debug, Clone)]
+struct PathGlob {
+    glob: Glob,
+    match_name: bool,
+}
+
+impl PathGlob {
+    fn new(pattern: &str) -> Result<Self, RgrepError> {
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        Ok(Self {
+            glob: Glob::new(pattern)?,
+            match_name: !pattern.contains('/'),
+        })
+    }
+
+    fn is_match(&self, relative: &str) -> bool {
+        if self.match_name {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            self.glob.is_match(name)
+        } else {
+            self.glob.is_match(relative)
+        }
+    }
+}
+
+/// Filters deciding which discovered files and directories are searched
+///
+/// `--include`, `--exclude` and `--exclude-dir` behave like GNU grep.
+/// `-g` globs behave like ripgrep: later globs take precedence, `!glob`
+/// excludes, and once any non-negated glob is given only files matching
+/// one of them are searched. `-t` and `-T` select and reject files by the
+/// globs of named file types.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Vec<PathGlob>,
+    exclude: Vec<PathGlob>,
+    exclude_dir: Vec<PathGlob>,
+    overrides: Vec<(PathGlob, bool)>,
+    type_include: Vec<PathGlob>,
+    type_exclude: Vec<PathGlob>,
+}
+
+impl PathFilter {
+    /// Compile the filters given in the configuration
+    pub fn new(config: &Config) -> Result<Self, RgrepError> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| PathGlob::new(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let overrides = config
+            .globs
+            .iter()
+            .map(|pattern| match pattern.strip_prefix('!') {
+                Some(negated) => PathGlob::new(negated).map(|glob| (glob, false)),
+                None => PathGlob::new(pattern).map(|glob| (glob, true)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let registry = TypeRegistry::with_additions(&config.type_add)?;
+        let type_globs = |names: &[String]| -> Result<Vec<PathGlob>, RgrepError> {
+            let mut globs = Vec::new();
+            for name in names {
+                globs.extend(compile(registry.globs(name)?)?);
+            }
+            Ok(globs)
+        };
+
+        Ok(Self {
+            include: compile(&config.include)?,
+            exclude: compile(&config.exclude)?,
+            exclude_dir: compile(&config.exclude_dir)?,
+            overrides,
+            type_include: type_globs(&config.types)?,
+            type_exclude: type_globs(&config.types_not)?,
+        })
+    }
+
+    /// Whether a file at `relative` (`/`-separated) should be searched
+    pub fn allows_file(&self, relative: &str) -> bool {
+        if !self.include.is_empty() && !self.include.iter().any(|g| g.is_match(relative)) {
+            return false;
+        }
+
+        if self.exclude.iter().any(|g| g.is_match(relative)) {
+            return false;
+        }
+
+        if !self.type_include.is_empty() && !self.type_include.iter().any(|g| g.is_match(relative))
+        {
+            return false;
+        }
+
+        if self.type_exclude.iter().any(|g| g.is_match(relative)) {
+            return false;
+        }
+
+        match self
+            .overrides
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(relative))
+        {
+            Some(&(_, allowed)) => allowed,
+            None => !self.overrides.iter().any(|&(_, allowed)| allowed),
+        }
+    }
+
+    /// Whether a file named on the command line should be searched
+    ///
+    /// As with GNU grep, file filters also apply to command-line files, with
+    /// the path as given in place of a path relative to a root.
+    pub fn allows_operand(&self, path: &Path) -> bool {
+        self.allows_file(&slash_path(path))
+    }
+
+    /// Whether a directory at `relative` (`/`-separated) should be entered
+    pub fn allows_dir(&self, relative: &str) -> bool {
+        if self.exclude_dir.iter().any(|g| g.is_match(relative)) {
+            return false;
+        }
+
+        // Only exclusions prune directories; inclusions select files
+        self.overrides
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(relative))
+            .is_none_or(|&(_, allowed)| allowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(configure: impl FnOnce(&mut Config)) -> PathFilter {
+        let mut config = Config::new("x".to_string(), Vec::new(), false, false);
+        configure(&mut config);
+        PathFilter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let f = filter(|c| {
+            c.include = vec!["*.rs".to_string()];
+            c.exclude = vec!["gen_*.rs".to_string()];
+        });
+        assert!(f.allows_file("src/main.rs"));
+        assert!(!f.allows_file("src/gen_table.rs"));
+        assert!(!f.allows_file("README.md"));
+        assert!(f.allows_dir("src"));
+    }
+
+    #[test]
+    fn test_command_line_files() {
+        let f = filter(|c| c.exclude = vec!["*.log".to_string(), "tmp/*".to_string()]);
+        assert!(!f.allows_operand(Path::new("a.log")));
+        assert!(!f.allows_operand(Path::new("/var/log/a.log")));
+        assert!(!f.allows_operand(Path::new("./tmp/a.txt")));
+        assert!(f.allows_operand(Path::new("a.txt")));
+    }
+
+    #[test]
+    fn test_exclude_dir() {
+        let f = filter(|c| c.exclude_dir = vec!["vendor".to_string(), "web/dist".to_string()]);
+        assert!(!f.allows_dir("vendor"));
+        assert!(!f.allows_dir("lib/vendor"));
+        assert!(!f.allows_dir("web/dist"));
+        assert!(f.allows_dir("dist"));
+        assert!(f.allows_file("vendor"));
+    }
+
+    #[test]
+    fn test_glob_overrides() {
+        let f = filter(|c| c.globs = vec!["!*.lock".to_string(), "!target".to_string()]);
+        assert!(f.allows_file("src/main.rs"));
+        assert!(!f.allows_file("Cargo.lock"));
+        assert!(!f.allows_dir("target"));
+
+        let f = filter(|c| c.globs = vec!["*.{rs,toml}".to_string(), "!build.rs".to_string()]);
+        assert!(f.allows_file("src/main.rs"));
+        assert!(f.allows_file("Cargo.toml"));
+        assert!(!f.allows_file("build.rs"));
+        assert!(!f.allows_file("README.md"));
+        assert!(f.allows_dir("src"));
+    }
+
+    #[test]
+    fn test_file_types() {
+        let f = filter(|c| c.types = vec!["rust".to_string(), "toml".to_string()]);
+        assert!(f.allows_file("src/main.rs"));
+        assert!(f.allows_file("Cargo.toml"));
+        assert!(!f.allows_file("README.md"));
+
+        let f = filter(|c| {
+            c.type_add = vec!["web:*.min.js".to_string()];
+            c.types_not = vec!["web".to_string()];
+        });
+        assert!(f.allows_file("app.js"));
+        assert!(!f.allows_file("dist/app.min.js"));
+    }
+
+    #[test]
+    fn test_unknown_file_type() {
+        let mut config = Config::new("x".to_string(), Vec::new(), false, false);
+        config.types = vec!["klingon".to_string()];
+        assert!(PathFilter::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let mut config = Config::new("x".to_string(), Vec::new(), false, false);
+        config.include = vec!["[oops".to_string()];
+        assert!(PathFilter::new(&config).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Glob pattern matching for paths
+
+use crate::errors::RgrepError;
+
+/// A single element of a compiled glob
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A literal character
+    Literal(char),
+    /// `?`: any single character except `/`
+    AnyChar,
+    /// `*`: any sequence of characters except `/`
+    Star,
+    /// `**/`: zero or more whole directories
+    AnyDirs,
+    /// `**` at the end of a pattern: anything, including `/`
+    AnyPath,
+    /// `[...]`: a character class
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A compiled glob pattern
+///
+/// Supports `*`, `?`, `**` as a whole path component, character classes
+/// (`[abc]`, `[a-z]`, `[!a]` / `[^a]`), brace alternation (`*.{rs,toml}`)
+/// and backslash escapes. Paths are matched as `/`-separated strings.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    /// One token sequence per brace expansion of the pattern
+    alternatives: Vec<Vec<Token>>,
+}
+
+impl Glob {
+    /// Compile a glob pattern
+    pub fn new(pattern: &str) -> Result<Self, RgrepError> {
+        let invalid = |reason: &str| {
+            RgrepError::invalid_args(format!("Invalid glob '{pattern}': {reason}"), false)
+        };
+
+        let expanded = expand_braces(pattern).ok_or_else(|| invalid("unclosed '{'"))?;
+        let alternatives = expanded
+            .iter()
+            .map(|alternative| tokenize(alternative).map_err(invalid))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { alternatives })
+    }
+
+    /// Check whether the whole path matches the pattern
+    pub fn is_match(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        self.alternatives
+            .iter()
+            .any(|tokens| match_tokens(tokens, &path))
+    }
+}
+
+/// Expand brace alternations into plain patterns
+///
+/// Nested braces are supported; returns `None` if a `{` is never closed.
+fn expand_braces(pattern: &str) -> Option<Vec<String>> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    let mut in_class = false;
+
+    // Find the first unescaped `{` outside a character class
+    let open = loop {
+        match chars.get(i) {
+            None => return Some(vec![pattern.to_string()]),
+            Some('\\') => i += 1,
+            Some('[') if !in_class => in_class = true,
+            Some(']') if in_class => in_class = false,
+            Some('{') if !in_class => break i,
+            _ => {}
+        }
+        i += 1;
+    };
+
+    // Split the body on top-level commas up to the matching `}`
+    let mut depth = 0;
+    let mut parts = Vec::new();
+    let mut start = open + 1;
+    i = open + 1;
+
+    let close = loop {
+        match chars.get(i)? {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' => {
+                parts.push(&chars[start..i]);
+                break i;
+            }
+            ',' if depth == 0 => {
+                parts.push(&chars[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    };
+
+    let prefix: String = chars[..open].iter().collect();
+    let suffix: String = chars[close + 1..].iter().collect();
+
+    let mut expanded = Vec::new();
+    for part in parts {
+        let part: String = part.iter().collect();
+        expanded.extend(expand_braces(&format!("{prefix}{part}{suffix}"))?);
+    }
+
+    Some(expanded)
+}
+
+/// Compile a brace-free pattern into tokens
+fn tokenize(pattern: &str) -> Result<Vec<Token>, &'static str> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                let c = *chars.get(i + 1).ok_or("trailing backslash")?;
+                tokens.push(Token::Literal(c));
+                i += 2;
+            }
+            '?' => {
+                tokens.push(Token::AnyChar);
+                i += 1;
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let starts_component = i == 0 || chars[i - 1] == '/';
+                match chars.get(i + 2) {
+                    Some('/') if starts_component => {
+                        tokens.push(Token::AnyDirs);
+                        i += 3;
+                    }
+                    None if starts_component => {
+                        tokens.push(Token::AnyPath);
+                        i += 2;
+                    }
+                    // `**` inside a component behaves like `*`
+                    _ => {
+                        tokens.push(Token::Star);
+                        i += 2;
+                    }
+                }
+            }
+            '*' => {
+                tokens.push(Token::Star);
+                i += 1;
+            }
+            '[' => {
+                let (token, next) = parse_class(&chars, i + 1).ok_or("unclosed '['")?;
+                tokens.push(token);
+                i = next;
+            }
+            c => {
+                tokens.push(Token::Literal(c));
+                i += 1;
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a character class body starting after `[`
+///
+/// Returns the token and the index just past the closing `]`.
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start;
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+
+    loop {
+        let mut c = *chars.get(i)?;
+
+        // A `]` right after the opening bracket is literal
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        if c == '\\' {
+            i += 1;
+            c = *chars.get(i)?;
+        }
+        first = false;
+
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                ranges.push((c, end));
+                i += 3;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Backtracking matcher over the compiled tokens
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return path.is_empty();
+    };
+
+    match token {
+        Token::Literal(c) => path.first() == Some(c) && match_tokens(rest, &path[1..]),
+        Token::AnyChar => {
+            matches!(path.first(), Some(&c) if c != '/') && match_tokens(rest, &path[1..])
+        }
+        Token::Class { negated, ranges } => match path.first() {
+            Some(&c) if c != '/' => {
+                let in_class = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                in_class != *negated && match_tokens(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Star => {
+            let limit = path.iter().position(|&c| c == '/').unwrap_or(path.len());
+            (0..=limit).any(|skip| match_tokens(rest, &path[skip..]))
+        }
+        Token::AnyDirs => {
+            // Try zero directories, then every position just after a `/`
+            match_tokens(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &c)| c == '/')
+                    .any(|(idx, _)| match_tokens(rest, &path[idx + 1..]))
+        }
+        Token::AnyPath => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn test_star_and_question_mark() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(!is_match("*.rs", "src/main.rs"));
+        assert!(is_match("src/*.rs", "src/main.rs"));
+        assert!(is_match("?.txt", "a.txt"));
+        assert!(!is_match("?.txt", "ab.txt"));
+        assert!(!is_match("a?b", "a/b"));
+    }
+
+    #[test]
+    fn test_double_star() {
+        assert!(is_match("**/foo", "foo"));
+        assert!(is_match("**/foo", "a/b/foo"));
+        assert!(is_match("a/**/b", "a/b"));
+        assert!(is_match("a/**/b", "a/x/y/b"));
+        assert!(!is_match("a/**/b", "ab"));
+        assert!(is_match("build/**", "build/x/y.o"));
+        assert!(!is_match("build/**", "build"));
+        assert!(is_match("a**b", "axxb"));
+        assert!(!is_match("a**b", "a/b"));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(is_match("[abc].txt", "b.txt"));
+        assert!(!is_match("[abc].txt", "d.txt"));
+        assert!(is_match("file[0-9]", "file7"));
+        assert!(is_match("[!a-c]x", "dx"));
+        assert!(!is_match("[^a-c]x", "bx"));
+        assert!(is_match("[]]", "]"));
+        assert!(!is_match("[/]", "/"));
+    }
+
+    #[test]
+    fn test_brace_alternation() {
+        assert!(is_match("*.{rs,toml}", "Cargo.toml"));
+        assert!(is_match("*.{rs,toml}", "main.rs"));
+        assert!(!is_match("*.{rs,toml}", "main.py"));
+        assert!(is_match("{src,tests}/**/*.rs", "tests/a/b.rs"));
+        assert!(is_match("a{b,c{d,e}}f", "acef"));
+        assert!(is_match("a{,x}b", "ab"));
+        assert!(is_match(r"\{a,b\}", "{a,b}"));
+        assert!(is_match("[{]", "{"));
+        assert!(Glob::new("*.{rs").is_err());
+    }
+
+    #[test]
+    fn test_escapes_and_errors() {
+        assert!(is_match(r"\*.txt", "*.txt"));
+        assert!(!is_match(r"\*.txt", "a.txt"));
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("abc\\").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Ignore file support (`.gitignore`, `.ignore`, `.rgrepignore`)
+
+use std::fs;
+use std::path::Path;
+
+use crate::glob::Glob;
+
+/// Ignore files read from each directory, lowest precedence first
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".rgrepignore"];
+
+/// A single pattern line of an ignore file
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    /// `!pattern`: re-include paths excluded by an earlier rule
+    negated: bool,
+    /// `pattern/`: only match directories
+    dir_only: bool,
+}
+
+/// Rules parsed from one or more ignore files of a single directory
+#[derive(Debug, Clone, Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    /// Parse ignore rules using gitignore syntax
+    ///
+    /// Invalid patterns are skipped, as git does.
+    pub fn parse(contents: &str) -> Self {
+        let mut gitignore = Self::default();
+        gitignore.add_rules(contents);
+        gitignore
+    }
+
+    /// Read the standard ignore files of `dir`, if any exist
+    pub fn from_dir(dir: &Path) -> Option<Self> {
+        let mut gitignore = Self::default();
+
+        for name in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(dir.join(name)) {
+                gitignore.add_rules(&contents);
+            }
+        }
+
+        (!gitignore.is_empty()).then_some(gitignore)
+    }
+
+    /// Read a single ignore file such as `.git/info/exclude`
+    pub fn from_file(path: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        let gitignore = Self::parse(&contents);
+        (!gitignore.is_empty()).then_some(gitignore)
+    }
+
+    /// Whether no rules were found
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Append the rules of one ignore file; later rules take precedence
+    fn add_rules(&mut self, contents: &str) {
+        self.rules.extend(contents.lines().filter_map(parse_rule));
+    }
+
+    /// Decide whether a path is ignored
+    ///
+    /// `path` is `/`-separated and relative to the directory holding the
+    /// ignore file. Returns `Some(true)` if ignored, `Some(false)` if
+    /// explicitly re-included and `None` if no rule matches.
+    pub fn matched(&self, path: &str, is_dir: bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(path))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Parse one line of an ignore file into a rule
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let line = trim_unescaped_trailing_spaces(line);
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+
+    // A slash anywhere but the end anchors the pattern to this directory
+    let glob = if let Some(anchored) = pattern.strip_prefix('/') {
+        Glob::new(anchored)
+    } else if pattern.contains('/') {
+        Glob::new(pattern)
+    } else {
+        Glob::new(&format!("**/{pattern}"))
+    };
+
+    glob.ok().map(|glob| Rule {
+        glob,
+        negated,
+        dir_only,
+    })
+}
+
+/// Remove trailing spaces unless they are escaped with a backslash
+fn trim_unescaped_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unanchored_patterns_match_at_any_depth() {
+        let ignore = Gitignore::parse("target\n*.log\n");
+        assert_eq!(ignore.matched("target", true), Some(true));
+        assert_eq!(ignore.matched("a/b/target", true), Some(true));
+        assert_eq!(ignore.matched("logs/app.log", false), Some(true));
+        assert_eq!(ignore.matched("src/main.rs", false), None);
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let ignore = Gitignore::parse("/build\ndocs/*.html\n");
+        assert_eq!(ignore.matched("build", true), Some(true));
+        assert_eq!(ignore.matched("src/build", true), None);
+        assert_eq!(ignore.matched("docs/index.html", false), Some(true));
+        assert_eq!(ignore.matched("docs/api/index.html", false), None);
+    }
+
+    #[test]
+    fn test_directory_only_patterns() {
+        let ignore = Gitignore::parse("cache/\n");
+        assert_eq!(ignore.matched("cache", true), Some(true));
+        assert_eq!(ignore.matched("cache", false), None);
+    }
+
+    #[test]
+    fn test_negation_last_rule_wins() {
+        let ignore = Gitignore::parse("*.log\n!keep.log\n");
+        assert_eq!(ignore.matched("debug.log", false), Some(true));
+        assert_eq!(ignore.matched("keep.log", false), Some(false));
+    }
+
+    #[test]
+    fn test_comments_blank_lines_and_escapes() {
+        let ignore = Gitignore::parse("# comment\n\n\\#hash\n\\!bang\ntrailing   \nspace\\ \n");
+        assert_eq!(ignore.matched("# comment", false), None);
+        assert_eq!(ignore.matched("#hash", false), Some(true));
+        assert_eq!(ignore.matched("!bang", false), Some(true));
+        assert_eq!(ignore.matched("trailing", false), Some(true));
+        assert_eq!(ignore.matched("space ", false), Some(true));
+    }
+}
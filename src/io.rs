@@ -1,43 +1,87 @@
 //! I/O operations and file handling
 
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
 use crate::errors::RgrepError;
+use crate::search::STDIN_LABEL;
 
-/// Input source with its name and line iterator
+/// Input source with its name and reader
 pub struct InputSource {
-    /// Source name for display (None for stdin)
-    pub name: Option<String>,
-    /// Iterator over lines
-    pub lines: Box<dyn Iterator<Item = io::Result<String>>>,
+    /// Path of the source (None for stdin)
+    pub name: Option<PathBuf>,
+    /// Reader over the raw input
+    pub reader: Box<dyn BufRead>,
+    /// Whether the first block of input looks like binary data
+    pub binary: bool,
+    /// Offset of standard input in its file before it was read, when
+    /// standard input is a regular file
+    pub stdin_offset: Option<u64>,
 }
 
 impl InputSource {
     /// Create input source from stdin
     pub fn stdin() -> Self {
+        let stdin_offset = stdin_offset();
         let stdin = io::stdin();
-        let reader = stdin.lock();
+        let mut reader = stdin.lock();
+        // A read error here will resurface when searching
+        let binary = is_binary(&mut reader).unwrap_or(false);
         Self {
             name: None,
-            lines: Box::new(normalize_lines(reader.lines())),
+            reader: Box::new(reader),
+            binary,
+            stdin_offset,
         }
     }
 
     /// Create input source from file
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, RgrepError> {
-        let path_str = path.as_ref().to_string_lossy().to_string();
-        let file = File::open(&path).map_err(|e| RgrepError::io_error(&path_str, e))?;
-        let reader = BufReader::new(file);
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy().to_string();
+        let file = File::open(path).map_err(|e| RgrepError::io_error(&path_str, e))?;
+
+        // Opening a directory succeeds on some platforms; reading it does not
+        let metadata = file
+            .metadata()
+            .map_err(|e| RgrepError::io_error(&path_str, e))?;
+        if metadata.is_dir() {
+            let error = io::Error::new(io::ErrorKind::IsADirectory, "Is a directory");
+            return Err(RgrepError::io_error(&path_str, error));
+        }
+        let mut reader = BufReader::new(file);
+        let binary = is_binary(&mut reader).map_err(|e| RgrepError::io_error(&path_str, e))?;
 
         Ok(Self {
-            name: Some(path_str),
-            lines: Box::new(normalize_lines(reader.lines())),
+            name: Some(path.to_path_buf()),
+            reader: Box::new(reader),
+            binary,
+            stdin_offset: None,
         })
     }
 }
 
+/// The bytes of a path, for output
+///
+/// On Unix these are the bytes the file system has, so a name that is not
+/// valid UTF-8 still names its file. Elsewhere such names are converted
+/// lossily.
+pub fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        Cow::Borrowed(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    match path.to_string_lossy() {
+        Cow::Borrowed(name) => Cow::Borrowed(name.as_bytes()),
+        Cow::Owned(name) => Cow::Owned(name.into_bytes()),
+    }
+}
+
 /// Create appropriate input source based on file path
 pub fn create_input_source(file_path: Option<&str>) -> Result<InputSource, RgrepError> {
     match file_path {
@@ -46,17 +90,73 @@ pub fn create_input_source(file_path: Option<&str>) -> Result<InputSource, Rgrep
     }
 }
 
-/// Normalize line endings (remove trailing \r)
-fn normalize_lines<I>(lines: I) -> impl Iterator<Item = io::Result<String>>
-where
-    I: Iterator<Item = io::Result<String>>,
-{
-    lines.map(|line_result| {
-        line_result.map(|mut line| {
-            if line.ends_with('\r') {
-                line.pop();
-            }
-            line
-        })
-    })
+/// Move standard input to `offset` in its file
+///
+/// Whoever reads standard input next (such as the rest of a shell script)
+/// continues from there, even though more was read into our buffers.
+pub fn seek_stdin(offset: u64) -> io::Result<()> {
+    match stdin_file() {
+        Some(mut file) => file.seek(SeekFrom::Start(offset)).map(drop),
+        None => Ok(()),
+    }
+}
+
+/// Current offset of standard input, if it is a regular file
+fn stdin_offset() -> Option<u64> {
+    let mut file = stdin_file()?;
+    if !file.metadata().ok()?.is_file() {
+        return None;
+    }
+    file.stream_position().ok()
+}
+
+/// A duplicate of the standard input descriptor, sharing its offset
+#[cfg(unix)]
+fn stdin_file() -> Option<File> {
+    use std::os::fd::AsFd;
+
+    let fd = io::stdin().as_fd().try_clone_to_owned().ok()?;
+    Some(File::from(fd))
+}
+
+/// Repositioning standard input is only supported on Unix
+#[cfg(not(unix))]
+fn stdin_file() -> Option<File> {
+    None
+}
+
+/// Read patterns from a file, one per line (`-` reads stdin)
+///
+/// Empty patterns are rejected as with `-e`, since one would select every
+/// line; a blank line in the file is an error rather than being skipped.
+/// An empty file gives no patterns.
+pub fn read_patterns(path: &str) -> Result<Vec<String>, RgrepError> {
+    let (label, reader): (&str, Box<dyn BufRead>) = if path == "-" {
+        (STDIN_LABEL, Box::new(io::stdin().lock()))
+    } else {
+        let file = File::open(path).map_err(|e| RgrepError::io_error(path, e))?;
+        (path, Box::new(BufReader::new(file)))
+    };
+
+    let mut patterns = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| RgrepError::io_error(label, e))?;
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if line.is_empty() {
+            return Err(RgrepError::invalid_args(
+                format!("Pattern cannot be empty (line {} of {label})", index + 1),
+                false,
+            ));
+        }
+        patterns.push(line.to_string());
+    }
+
+    Ok(patterns)
+}
+
+/// Detect binary content by looking for a NUL byte in the first buffered block
+///
+/// The block stays buffered, so no input is lost.
+pub fn is_binary<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    Ok(reader.fill_buf()?.contains(&0))
 }
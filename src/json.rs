@@ -0,0 +1,295 @@
+//! JSON Lines output (`--json`)
+//!
+//! Each searched input produces a `begin` event, a `match` or `context`
+//! event per printed line and an `end` event with its statistics; a final
+//! `summary` event totals the whole search. Every event is one JSON object
+//! on its own line.
+//!
+//! Text that may not be valid UTF-8 (paths, line contents, matched spans) is
+//! written as `{"text": "..."}` when it is valid and `{"bytes": "..."}`
+//! holding standard base64 otherwise. Spans and offsets are in bytes.
+
+use std::path::Path;
+
+use crate::cli::ColumnUnit;
+use crate::io::path_bytes;
+use crate::search::{column_number, MatchResult, SearchStats};
+
+/// Format the event starting the results for an input
+///
+/// Standard input has a `null` path.
+pub fn format_begin(source_name: Option<&Path>) -> Vec<u8> {
+    let mut output = event("begin", source_name);
+    output.push(b'}');
+    output
+}
+
+/// Format the event for a selected line
+///
+/// The column is that of the first match, counted in `unit`; lines selected
+/// with `-v` have no match, so their column is `null`.
+pub fn format_match(
+    match_result: &MatchResult,
+    source_name: Option<&Path>,
+    unit: ColumnUnit,
+) -> Vec<u8> {
+    let line = &match_result.line;
+    let mut output = event("match", source_name);
+    push_position(&mut output, match_result);
+
+    output.extend_from_slice(b",\"column\":");
+    match match_result.spans.first() {
+        Some(span) => push_number(&mut output, column_number(line, span.start, unit)),
+        None => output.extend_from_slice(b"null"),
+    }
+
+    output.extend_from_slice(b",\"text\":");
+    push_data(&mut output, line);
+
+    output.extend_from_slice(b",\"spans\":[");
+    for (idx, span) in match_result.spans.iter().enumerate() {
+        if idx > 0 {
+            output.push(b',');
+        }
+        output.extend_from_slice(b"{\"start\":");
+        push_number(&mut output, span.start);
+        output.extend_from_slice(b",\"end\":");
+        push_number(&mut output, span.end);
+        output.extend_from_slice(b",\"text\":");
+        push_data(&mut output, &line[span.clone()]);
+        output.push(b'}');
+    }
+    output.extend_from_slice(b"]}");
+    output
+}
+
+/// Format the event for a line printed as context
+pub fn format_context(match_result: &MatchResult, source_name: Option<&Path>) -> Vec<u8> {
+    let mut output = event("context", source_name);
+    push_position(&mut output, match_result);
+    output.extend_from_slice(b",\"text\":");
+    push_data(&mut output, &match_result.line);
+    output.push(b'}');
+    output
+}
+
+/// Format the event ending the results for an input
+pub fn format_end(source_name: Option<&Path>, stats: &SearchStats) -> Vec<u8> {
+    let mut output = event("end", source_name);
+    output.extend_from_slice(b",\"stats\":{\"selected_lines\":");
+    push_number(&mut output, stats.selected_lines);
+    output.extend_from_slice(b",\"matches\":");
+    push_number(&mut output, stats.matches);
+    output.extend_from_slice(b"}}");
+    output
+}
+
+/// Format the event closing the output with totals for all inputs
+pub fn format_summary(stats: &SearchStats) -> Vec<u8> {
+    let mut output = b"{\"type\":\"summary\",\"stats\":{\"inputs\":".to_vec();
+    push_number(&mut output, stats.inputs);
+    output.extend_from_slice(b",\"matched_inputs\":");
+    push_number(&mut output, stats.matched_inputs);
+    output.extend_from_slice(b",\"selected_lines\":");
+    push_number(&mut output, stats.selected_lines);
+    output.extend_from_slice(b",\"matches\":");
+    push_number(&mut output, stats.matches);
+    output.extend_from_slice(b"}}");
+    output
+}
+
+/// Start an event object with its type and path, leaving it open
+fn event(kind: &str, source_name: Option<&Path>) -> Vec<u8> {
+    let mut output = b"{\"type\":".to_vec();
+    push_string(&mut output, kind);
+    output.extend_from_slice(b",\"path\":");
+    match source_name {
+        Some(name) => push_data(&mut output, &path_bytes(name)),
+        None => output.extend_from_slice(b"null"),
+    }
+    output
+}
+
+/// Append the line number and byte offset fields of a line
+fn push_position(output: &mut Vec<u8>, match_result: &MatchResult) {
+    output.extend_from_slice(b",\"line_number\":");
+    push_number(output, match_result.line_number);
+    output.extend_from_slice(b",\"byte_offset\":");
+    push_number(output, match_result.byte_offset);
+}
+
+fn push_number(output: &mut Vec<u8>, number: usize) {
+    output.extend_from_slice(number.to_string().as_bytes());
+}
+
+/// Append arbitrary bytes as a `text` or `bytes` object
+fn push_data(output: &mut Vec<u8>, data: &[u8]) {
+    match std::str::from_utf8(data) {
+        Ok(text) => {
+            output.extend_from_slice(b"{\"text\":");
+            push_string(output, text);
+        }
+        Err(_) => {
+            output.extend_from_slice(b"{\"bytes\":\"");
+            push_base64(output, data);
+            output.push(b'"');
+        }
+    }
+    output.push(b'}');
+}
+
+/// Append a quoted JSON string
+fn push_string(output: &mut Vec<u8>, text: &str) {
+    output.push(b'"');
+    for c in text.chars() {
+        match c {
+            '"' => output.extend_from_slice(b"\\\""),
+            '\\' => output.extend_from_slice(b"\\\\"),
+            '\n' => output.extend_from_slice(b"\\n"),
+            '\r' => output.extend_from_slice(b"\\r"),
+            '\t' => output.extend_from_slice(b"\\t"),
+            c if c < ' ' => output.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes()),
+            c => {
+                let mut buf = [0; 4];
+                output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    output.push(b'"');
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Append `data` in standard, padded base64
+fn push_base64(output: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                let sextet = (group >> (18 - 6 * idx)) & 0x3f;
+                output.push(BASE64_ALPHABET[sextet as usize]);
+            } else {
+                output.push(b'=');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base64(data: &[u8]) -> String {
+        let mut output = Vec::new();
+        push_base64(&mut output, data);
+        String::from_utf8(output).unwrap()
+    }
+
+    fn line(text: &[u8], spans: &[(usize, usize)]) -> MatchResult {
+        MatchResult {
+            line: text.to_vec(),
+            line_number: 4,
+            byte_offset: 30,
+            matched: !spans.is_empty(),
+            spans: spans.iter().map(|&(start, end)| start..end).collect(),
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"\xff\xfe\x80"), "//6A");
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let mut output = Vec::new();
+        push_string(&mut output, "a\"b\\c\td\u{1}é");
+        assert_eq!(output, "\"a\\\"b\\\\c\\td\\u0001é\"".as_bytes());
+    }
+
+    #[test]
+    fn test_format_match() {
+        let result = line("déjà foo: 1".as_bytes(), &[(7, 10)]);
+        assert_eq!(
+            String::from_utf8(format_match(
+                &result,
+                Some(Path::new("a:b.txt")),
+                ColumnUnit::Chars
+            ))
+            .unwrap(),
+            r#"{"type":"match","path":{"text":"a:b.txt"},"line_number":4,"byte_offset":30,"#
+                .to_string()
+                + r#""column":6,"text":{"text":"déjà foo: 1"},"#
+                + r#""spans":[{"start":7,"end":10,"text":{"text":"foo"}}]}"#
+        );
+
+        let inverted = line(b"x", &[]);
+        assert_eq!(
+            String::from_utf8(format_match(&inverted, None, ColumnUnit::Bytes)).unwrap(),
+            r#"{"type":"match","path":null,"line_number":4,"byte_offset":30,"column":null,"#
+                .to_string()
+                + r#""text":{"text":"x"},"spans":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_non_utf8_data_is_base64() {
+        let result = line(b"caf\xe9 foo", &[(5, 8)]);
+        let output = String::from_utf8(format_match(&result, None, ColumnUnit::Chars)).unwrap();
+        assert!(output.contains(r#""text":{"bytes":"Y2Fm6SBmb28="}"#));
+        assert!(output.contains(r#""text":{"text":"foo"}"#));
+
+        let output = String::from_utf8(format_context(&result, Some(Path::new("f")))).unwrap();
+        assert_eq!(
+            output,
+            r#"{"type":"context","path":{"text":"f"},"line_number":4,"byte_offset":30,"text":{"bytes":"Y2Fm6SBmb28="}}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_is_base64() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
+        assert_eq!(
+            format_begin(Some(path)),
+            br#"{"type":"begin","path":{"bytes":"Y2Fm6S50eHQ="}}"#
+        );
+    }
+
+    #[test]
+    fn test_begin_end_and_summary() {
+        let stats = SearchStats {
+            inputs: 3,
+            matched_inputs: 2,
+            selected_lines: 5,
+            matches: 7,
+        };
+        assert_eq!(
+            format_begin(Some(Path::new("f"))),
+            br#"{"type":"begin","path":{"text":"f"}}"#
+        );
+        assert_eq!(
+            format_end(None, &stats),
+            br#"{"type":"end","path":null,"stats":{"selected_lines":5,"matches":7}}"#
+        );
+        assert_eq!(
+            format_summary(&stats),
+            br#"{"type":"summary","stats":{"inputs":3,"matched_inputs":2,"selected_lines":5,"matches":7}}"#
+        );
+    }
+}
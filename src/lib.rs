@@ -4,11 +4,23 @@
 //! with support for case-insensitive matching and line number display.
 
 pub mod app;
+pub mod casefold;
 pub mod cli;
+pub mod color;
+pub mod context;
 pub mod errors;
+pub mod filter;
+pub mod glob;
+pub mod ignore;
 pub mod io;
+pub mod json;
+pub mod ordered;
+pub mod rewrite;
 pub mod search;
+pub mod searcher;
+pub mod types;
+pub mod walk;
 
 // Re-export commonly used types
-pub use cli::{CliAction, Config};
+pub use cli::{BinaryFiles, CliAction, ColumnUnit, Config, OutputMode, SortBy};
 pub use errors::{ExitCode, RgrepError};
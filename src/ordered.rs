@@ -0,0 +1,313 @@
+//! Output of concurrent searches, printed in input order
+//!
+//! Each search job writes through its own [`JobWriter`]. The job at the head
+//! of the order writes straight to the output, so its results stream as they
+//! are found; later jobs buffer up to [`BUFFER_LIMIT`] bytes and then wait
+//! for their turn. Jobs are only started within a window after the head (see
+//! [`OrderedOutput::wait_for_slot`]), which bounds the output held by
+//! finished jobs waiting to be printed.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::errors::RgrepError;
+use crate::search::SearchStats;
+
+/// Output a job buffers before waiting to become the head
+pub const BUFFER_LIMIT: usize = 256 * 1024;
+
+/// Output shared by numbered jobs, printed in job order
+pub struct OrderedOutput<W> {
+    state: Mutex<State<W>>,
+    /// Signalled whenever the head moves or the search stops
+    turn: Condvar,
+    /// Index of the job whose output is printed now
+    head: AtomicUsize,
+    stopped: AtomicBool,
+    /// How many jobs from the head on may run at once
+    window: usize,
+    /// Stop at the first input with a selected line (`-q`)
+    stop_on_match: bool,
+}
+
+struct State<W> {
+    out: W,
+    /// Finished jobs after the head, waiting for their turn
+    finished: BTreeMap<usize, Finished>,
+    stats: SearchStats,
+    /// First error writing the output
+    error: Option<io::Error>,
+}
+
+struct Finished {
+    output: Vec<u8>,
+    result: Result<SearchStats, RgrepError>,
+}
+
+impl<W: Write> OrderedOutput<W> {
+    pub fn new(out: W, window: usize, stop_on_match: bool) -> Self {
+        Self {
+            state: Mutex::new(State {
+                out,
+                finished: BTreeMap::new(),
+                stats: SearchStats::default(),
+                error: None,
+            }),
+            turn: Condvar::new(),
+            head: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+            window: window.max(1),
+            stop_on_match,
+        }
+    }
+
+    /// Wait until job `index` may start
+    ///
+    /// Returns false if the search stopped instead.
+    pub fn wait_for_slot(&self, index: usize) -> bool {
+        let mut state = self.lock();
+        while index >= self.head.load(Ordering::Acquire) + self.window && !self.is_stopped() {
+            state = self.turn.wait(state).expect("output state poisoned");
+        }
+        !self.is_stopped()
+    }
+
+    /// Writer for the output of job `index`
+    pub fn writer(&self, index: usize) -> JobWriter<'_, W> {
+        JobWriter {
+            output: self,
+            index,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Whether the search stopped, after a write error or with `-q`
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// The output, the statistics of the printed jobs and any write error
+    pub fn into_parts(self) -> (W, SearchStats, io::Result<()>) {
+        let state = self.state.into_inner().expect("output state poisoned");
+        let mut out = state.out;
+        let result = match state.error {
+            Some(error) => Err(error),
+            None => out.flush(),
+        };
+        (out, state.stats, result)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<W>> {
+        self.state.lock().expect("output state poisoned")
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.turn.notify_all();
+    }
+
+    /// Wait until job `index` is the head
+    fn wait_for_turn(&self, index: usize) -> io::Result<()> {
+        let mut state = self.lock();
+        while self.head.load(Ordering::Acquire) != index && !self.is_stopped() {
+            state = self.turn.wait(state).expect("output state poisoned");
+        }
+        if self.is_stopped() {
+            return Err(stopped());
+        }
+        Ok(())
+    }
+
+    /// Write to the output, stopping the search if that fails
+    fn write_out(&self, state: &mut State<W>, data: &[u8]) -> io::Result<()> {
+        if let Err(error) = state.out.write_all(data) {
+            state.error.get_or_insert(error);
+            self.stop();
+            return Err(stopped());
+        }
+        Ok(())
+    }
+
+    /// Record a finished job, then print every finished job from the head on
+    fn finish(&self, index: usize, output: Vec<u8>, result: Result<SearchStats, RgrepError>) {
+        let mut state = self.lock();
+
+        // Nothing is printed with `-q`, so any match ends the search at once
+        if let Ok(stats) = &result {
+            if self.stop_on_match && stats.matched_inputs > 0 {
+                state.stats += *stats;
+                self.stop();
+                return;
+            }
+        }
+
+        state.finished.insert(index, Finished { output, result });
+
+        let mut head = self.head.load(Ordering::Acquire);
+        while let Some(job) = state.finished.remove(&head) {
+            if self.write_out(&mut state, &job.output).is_err() {
+                return;
+            }
+            match job.result {
+                Ok(stats) => state.stats += stats,
+                Err(error) => eprintln!("{error}"),
+            }
+            head += 1;
+        }
+
+        self.head.store(head, Ordering::Release);
+        self.turn.notify_all();
+    }
+}
+
+/// Writer for one job's output
+///
+/// Writes fail once the search has stopped, so the job ends early.
+pub struct JobWriter<'a, W: Write> {
+    output: &'a OrderedOutput<W>,
+    index: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> JobWriter<'_, W> {
+    /// Hand over the job's result, to be reported in order
+    ///
+    /// A job that could not be searched passes its error, which is printed
+    /// to standard error in its turn.
+    pub fn finish(self, result: Result<SearchStats, RgrepError>) {
+        self.output.finish(self.index, self.buffer, result);
+    }
+
+    fn is_head(&self) -> bool {
+        self.output.head.load(Ordering::Acquire) == self.index
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        let mut state = self.output.lock();
+        self.output.write_out(&mut state, &self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for JobWriter<'_, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.output.is_stopped() {
+            return Err(stopped());
+        }
+
+        self.buffer.extend_from_slice(data);
+
+        if self.is_head() {
+            // Pass whole lines on (NUL-terminated with `-z`), so the output
+            // lock is taken once per line rather than once per write
+            if memchr::memchr2(b'\n', b'\0', data).is_some() || self.buffer.len() > BUFFER_LIMIT {
+                self.write_buffer()?;
+            }
+        } else if self.buffer.len() > BUFFER_LIMIT {
+            self.output.wait_for_turn(self.index)?;
+            self.write_buffer()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.is_head() {
+            self.write_buffer()?;
+            self.output.lock().out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Error ending the writes of a job once the search has stopped
+///
+/// Not `Interrupted`, which `write_all` would retry.
+fn stopped() -> io::Error {
+    io::Error::other("search stopped")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    fn printed(output: &OrderedOutput<Vec<u8>>) -> Vec<u8> {
+        output.lock().out.clone()
+    }
+
+    fn stats(selected_lines: usize) -> Result<SearchStats, RgrepError> {
+        Ok(SearchStats::for_input(selected_lines, selected_lines))
+    }
+
+    #[test]
+    fn test_output_in_job_order() {
+        let output = OrderedOutput::new(Vec::new(), 4, false);
+
+        let mut second = output.writer(1);
+        second.write_all(b"b\n").unwrap();
+        second.finish(stats(1));
+        output
+            .writer(2)
+            .finish(Err(RgrepError::app_error("unreadable")));
+        assert!(printed(&output).is_empty());
+
+        // The head writes straight through
+        let mut first = output.writer(0);
+        first.write_all(b"a\n").unwrap();
+        assert_eq!(printed(&output), b"a\n");
+        first.finish(stats(2));
+
+        let (out, stats, result) = output.into_parts();
+        assert_eq!(out, b"a\nb\n");
+        assert_eq!((stats.inputs, stats.selected_lines), (2, 3));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_buffer_limit_waits_for_turn() {
+        let output = OrderedOutput::new(Vec::new(), 4, false);
+
+        thread::scope(|scope| {
+            let later = scope.spawn(|| {
+                let mut writer = output.writer(1);
+                writer.write_all(&vec![b'x'; BUFFER_LIMIT + 1]).unwrap();
+                writer.finish(stats(1));
+            });
+
+            thread::sleep(Duration::from_millis(50));
+            assert!(!later.is_finished());
+            assert!(printed(&output).is_empty());
+
+            output.writer(0).finish(stats(0));
+            later.join().unwrap();
+        });
+
+        assert_eq!(printed(&output).len(), BUFFER_LIMIT + 1);
+    }
+
+    #[test]
+    fn test_window_and_stop_on_match() {
+        let output = OrderedOutput::new(Vec::new(), 2, true);
+        assert!(output.wait_for_slot(1));
+
+        thread::scope(|scope| {
+            let waiting = scope.spawn(|| output.wait_for_slot(2));
+            thread::sleep(Duration::from_millis(50));
+            assert!(!waiting.is_finished());
+
+            output.writer(0).finish(stats(0));
+            assert!(waiting.join().unwrap());
+        });
+
+        // A match anywhere stops the search, and later writes fail
+        output.writer(3).finish(stats(1));
+        assert!(output.is_stopped());
+        assert!(!output.wait_for_slot(2));
+        assert!(output.writer(1).write_all(b"x").is_err());
+        assert_eq!(output.into_parts().1.matched_inputs, 1);
+    }
+}
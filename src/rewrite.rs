@@ -0,0 +1,477 @@
+//! In-place rewriting of files (`--replace TEXT --in-place`)
+//!
+//! Rewriting happens in three steps so that a failure leaves every file as
+//! it was: all files are read and their new contents computed, then each is
+//! staged as a temporary file next to it, and only then are the temporary
+//! files renamed over the originals. If a rename (or a backup) fails, the
+//! files already replaced are restored from the contents read at the start.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use tempfile::{NamedTempFile, TempPath};
+
+use crate::cli::{BinaryFiles, Config};
+use crate::errors::RgrepError;
+use crate::io::is_binary;
+use crate::search::{replace_matches, Matcher};
+use crate::searcher::BufferSearcher;
+
+/// Suffix of the backup copies kept with `--backup`
+pub const BACKUP_SUFFIX: &str = ".bak";
+
+/// Number of unchanged lines shown around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// The new contents computed for one file
+#[derive(Debug)]
+pub struct FileRewrite {
+    /// Path as given or found, used in messages and diffs
+    pub path: String,
+    /// File actually written, with symbolic links resolved
+    target: PathBuf,
+    permissions: fs::Permissions,
+    /// Contents when the file was read
+    pub original: Vec<u8>,
+    /// Contents with every match replaced
+    pub replaced: Vec<u8>,
+    /// The lines that changed, in order
+    changes: Vec<LineChange>,
+}
+
+/// A line of the original file and what it became
+#[derive(Debug)]
+struct LineChange {
+    /// Line index (0-based) in the original
+    index: usize,
+    /// New contents, with the original terminator; more than one line when
+    /// the replacement contains the terminator
+    replaced: Vec<u8>,
+}
+
+impl FileRewrite {
+    /// The file written, with symbolic links resolved
+    ///
+    /// Two paths naming the same file have the same target.
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+}
+
+/// Compute the rewritten contents of a file
+///
+/// Returns `None` when nothing would change, including for binary files that
+/// are not searched as text.
+pub fn plan_rewrite(
+    path: &Path,
+    matcher: &dyn Matcher,
+    replacement: &[u8],
+    config: &Config,
+) -> Result<Option<FileRewrite>, RgrepError> {
+    let display = path.to_string_lossy();
+    let io_error = |e| RgrepError::io_error(display.as_ref(), e);
+    let target = fs::canonicalize(path).map_err(io_error)?;
+    let metadata = fs::metadata(&target).map_err(io_error)?;
+    if metadata.is_dir() {
+        let error = io::Error::new(io::ErrorKind::IsADirectory, "Is a directory");
+        return Err(io_error(error));
+    }
+
+    let original = fs::read(&target).map_err(io_error)?;
+    let binary = is_binary(&mut &original[..]).map_err(io_error)?;
+    if binary && !config.null_data && config.binary_files != BinaryFiles::Text {
+        return Ok(None);
+    }
+
+    let changes = line_changes(&original, matcher, replacement, config);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    let replaced = splice(&original, &changes, config.line_terminator());
+
+    Ok(Some(FileRewrite {
+        path: display.to_string(),
+        target,
+        permissions: metadata.permissions(),
+        original,
+        replaced,
+        changes,
+    }))
+}
+
+/// Replace the matches in every selected line of `data`
+///
+/// Only line contents are rewritten; line terminators (including `\r\n`)
+/// are kept as they were. Lines left the same are not returned.
+fn line_changes(
+    data: &[u8],
+    matcher: &dyn Matcher,
+    replacement: &[u8],
+    config: &Config,
+) -> Vec<LineChange> {
+    let searcher = BufferSearcher::new(data, matcher)
+        .invert_match(config.invert_match)
+        .max_count(config.max_count)
+        .line_terminator(config.line_terminator());
+
+    searcher
+        .filter(|m| !m.spans.is_empty())
+        .filter_map(|match_result| {
+            let start = match_result.byte_offset;
+            let content_end = start + match_result.line.len();
+            let end = data[content_end..]
+                .iter()
+                .position(|&b| b == config.line_terminator())
+                .map_or(data.len(), |pos| content_end + pos + 1);
+            let index = match_result.line_number - 1;
+
+            let mut replaced = replace_matches(match_result, matcher, replacement).line;
+            replaced.extend_from_slice(&data[content_end..end]);
+            (replaced != data[start..end]).then_some(LineChange { index, replaced })
+        })
+        .collect()
+}
+
+/// Build the new contents by putting each changed line in place
+fn splice(data: &[u8], changes: &[LineChange], terminator: u8) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len());
+    let mut changes = changes.iter().peekable();
+
+    for (index, line) in split_lines(data, terminator).into_iter().enumerate() {
+        match changes.next_if(|change| change.index == index) {
+            Some(change) => output.extend_from_slice(&change.replaced),
+            None => output.extend_from_slice(line),
+        }
+    }
+
+    output
+}
+
+/// Write every rewrite, or none of them
+///
+/// With `backup`, each original is first copied next to it with
+/// [`BACKUP_SUFFIX`]; existing backups are never overwritten, so one is an
+/// error before anything is written. On error, files already replaced are
+/// restored, new backups are removed and the first error is returned.
+///
+/// Each target must appear only once, or its backup would be taken from the
+/// file already rewritten.
+pub fn apply_rewrites(rewrites: &[FileRewrite], backup: bool) -> Result<(), RgrepError> {
+    if backup {
+        for rewrite in rewrites {
+            let backup_path = backup_path(&rewrite.target);
+            if backup_path.symlink_metadata().is_ok() {
+                return Err(RgrepError::io_error(
+                    &rewrite.path,
+                    backup_exists(&backup_path),
+                ));
+            }
+        }
+    }
+
+    // Temporary files are removed when dropped, so an error here needs no
+    // cleaning up. Only their paths are kept, so the number of open files
+    // does not grow with the number of rewrites.
+    let staged = rewrites
+        .iter()
+        .map(|rewrite| {
+            stage(&rewrite.target, &rewrite.replaced, &rewrite.permissions)
+                .map_err(|e| RgrepError::io_error(&rewrite.path, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut committed = Vec::new();
+    let mut backups = Vec::new();
+
+    for (rewrite, temp) in rewrites.iter().zip(staged) {
+        let result = commit(rewrite, temp, backup, &mut backups);
+        if let Err(error) = result {
+            roll_back(&committed, &backups);
+            return Err(RgrepError::io_error(&rewrite.path, error));
+        }
+        committed.push(rewrite);
+    }
+
+    Ok(())
+}
+
+/// Back up the original if asked, then move the staged file over it
+fn commit(
+    rewrite: &FileRewrite,
+    temp: TempPath,
+    backup: bool,
+    backups: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    if backup {
+        let backup_path = backup_path(&rewrite.target);
+        write_backup(rewrite, &backup_path)?;
+        backups.push(backup_path);
+    }
+
+    temp.persist(&rewrite.target).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Restore replaced files and remove the backups made before an error
+///
+/// This is best effort: a file that cannot be restored is reported, and the
+/// others are still restored.
+fn roll_back(committed: &[&FileRewrite], backups: &[PathBuf]) {
+    for rewrite in committed {
+        let restored = stage(&rewrite.target, &rewrite.original, &rewrite.permissions)
+            .and_then(|temp| temp.persist(&rewrite.target).map_err(|e| e.error));
+        if let Err(error) = restored {
+            eprintln!("{}", RgrepError::io_error(&rewrite.path, error));
+        }
+    }
+
+    for backup_path in backups {
+        let _ = fs::remove_file(backup_path);
+    }
+}
+
+/// Write `data` to a temporary file in the directory of `target`
+///
+/// Being on the same file system, it can later be renamed over `target`
+/// atomically. The file is closed once written; the returned path still
+/// removes it when dropped.
+fn stage(target: &Path, data: &[u8], permissions: &fs::Permissions) -> io::Result<TempPath> {
+    let dir = target.parent().unwrap_or(Path::new("."));
+    let mut temp = NamedTempFile::new_in(dir)?;
+    temp.write_all(data)?;
+    temp.as_file().set_permissions(permissions.clone())?;
+    temp.as_file().sync_all()?;
+    Ok(temp.into_temp_path())
+}
+
+/// Write the original contents to a new backup file
+///
+/// The backup is created exclusively, so one appearing since the check in
+/// [`apply_rewrites`] is not overwritten either.
+fn write_backup(rewrite: &FileRewrite, backup_path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(backup_path)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => backup_exists(backup_path),
+            _ => e,
+        })?;
+
+    let written = file
+        .write_all(&rewrite.original)
+        .and_then(|()| file.set_permissions(rewrite.permissions.clone()));
+    if written.is_err() {
+        let _ = fs::remove_file(backup_path);
+    }
+    written
+}
+
+fn backup_exists(backup_path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("backup {} already exists", backup_path.display()),
+    )
+}
+
+/// Path of the backup copy of `target`
+fn backup_path(target: &Path) -> PathBuf {
+    let mut path = target.as_os_str().to_owned();
+    path.push(BACKUP_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Format a unified diff between the original and rewritten contents
+///
+/// Lines are split at `terminator`; a last line without one is marked as in
+/// `diff -u`.
+pub fn format_diff(rewrite: &FileRewrite, terminator: u8) -> Vec<u8> {
+    let old = split_lines(&rewrite.original, terminator);
+    let mut output = format!("--- {}\n+++ {}\n", rewrite.path, rewrite.path).into_bytes();
+
+    // Lines added before the current hunk by replacements containing the
+    // terminator, to find where the hunk starts in the new file
+    let mut shift = 0;
+    let mut changes = rewrite.changes.iter().peekable();
+
+    while let Some(first) = changes.next() {
+        // Changes close enough for their context to touch share a hunk
+        let mut hunk = vec![first];
+        while let Some(next) =
+            changes.next_if(|next| next.index <= hunk[hunk.len() - 1].index + 2 * DIFF_CONTEXT + 1)
+        {
+            hunk.push(next);
+        }
+
+        let start = first.index.saturating_sub(DIFF_CONTEXT);
+        let end = (hunk[hunk.len() - 1].index + 1 + DIFF_CONTEXT).min(old.len());
+        let new_lines: Vec<Vec<&[u8]>> = hunk
+            .iter()
+            .map(|change| split_lines(&change.replaced, terminator))
+            .collect();
+        let added: usize = new_lines.iter().map(|lines| lines.len() - 1).sum();
+        let old_len = end - start;
+
+        output.extend_from_slice(
+            format!(
+                "@@ -{} +{} @@\n",
+                hunk_range(start, old_len),
+                hunk_range(start + shift, old_len + added)
+            )
+            .as_bytes(),
+        );
+        shift += added;
+
+        // Runs of adjacent changed lines show all removals, then all additions
+        let mut line = start;
+        let mut changed = hunk.iter().zip(&new_lines).peekable();
+        while line < end {
+            let mut run = Vec::new();
+            while let Some((_, lines)) = changed.next_if(|(change, _)| change.index == line) {
+                run.push(lines);
+                line += 1;
+            }
+
+            if run.is_empty() {
+                push_diff_line(&mut output, b' ', old[line], terminator);
+                line += 1;
+                continue;
+            }
+            for old_line in &old[line - run.len()..line] {
+                push_diff_line(&mut output, b'-', old_line, terminator);
+            }
+            for new_line in run.into_iter().flatten() {
+                push_diff_line(&mut output, b'+', new_line, terminator);
+            }
+        }
+    }
+
+    output
+}
+
+/// Format the `start,len` part of a hunk header from a 0-based start
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+/// Append one diff line, marking a last line without terminator
+fn push_diff_line(output: &mut Vec<u8>, marker: u8, line: &[u8], terminator: u8) {
+    output.push(marker);
+    match line.strip_suffix(&[terminator]) {
+        Some(content) => {
+            output.extend_from_slice(content);
+            output.push(b'\n');
+        }
+        None => {
+            output.extend_from_slice(line);
+            output.extend_from_slice(b"\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// Split data into lines, each keeping its terminator
+fn split_lines(data: &[u8], terminator: u8) -> Vec<&[u8]> {
+    data.split_inclusive(|&b| b == terminator).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::create_matcher;
+
+    /// Plan the rewrite of `data` as if it were a file named `f`
+    fn rewrite(data: &[u8], pattern: &str, replacement: &str) -> FileRewrite {
+        let config = Config::new(pattern.to_string(), Vec::new(), false, false);
+        let matcher = create_matcher(&config).unwrap();
+        let changes = line_changes(data, matcher.as_ref(), replacement.as_bytes(), &config);
+        FileRewrite {
+            path: "f".to_string(),
+            target: PathBuf::from("f"),
+            permissions: fs::metadata(".").unwrap().permissions(),
+            original: data.to_vec(),
+            replaced: splice(data, &changes, b'\n'),
+            changes,
+        }
+    }
+
+    fn diff(data: &str, pattern: &str, replacement: &str) -> String {
+        let rewrite = rewrite(data.as_bytes(), pattern, replacement);
+        String::from_utf8(format_diff(&rewrite, b'\n')).unwrap()
+    }
+
+    #[test]
+    fn test_failed_commit_rolls_back() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "foo\n").unwrap();
+        // Renaming a file over a directory fails once `a.txt` is committed
+        let blocker = dir.path().join("b");
+        fs::create_dir(&blocker).unwrap();
+
+        let permissions = fs::metadata(&file).unwrap().permissions();
+        let rewrite = |target: &Path| FileRewrite {
+            path: target.to_string_lossy().to_string(),
+            target: target.to_path_buf(),
+            permissions: permissions.clone(),
+            original: b"foo\n".to_vec(),
+            replaced: b"bar\n".to_vec(),
+            changes: Vec::new(),
+        };
+
+        let error = apply_rewrites(&[rewrite(&file), rewrite(&blocker)], true).unwrap_err();
+        assert!(matches!(error, RgrepError::IoError { ref path, .. } if path.ends_with("b")));
+
+        assert_eq!(fs::read(&file).unwrap(), b"foo\n");
+        assert!(blocker.is_dir());
+        // Only the file and the directory remain: no backups or temporary files
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_replace_keeps_terminators() {
+        let result = rewrite(b"a foo\r\nb\nfoo foo", "foo", "x");
+        assert_eq!(result.replaced, b"a x\r\nb\nx x");
+        assert_eq!(
+            result.changes.iter().map(|c| c.index).collect::<Vec<_>>(),
+            [0, 2]
+        );
+
+        // Replacing a match with itself changes nothing
+        assert!(rewrite(b"foo\n", "foo", "foo").changes.is_empty());
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        let data = "1\n2 foo\n3\n4\n5\n6\n7\n8\n9\n10 foo\n11 foo\n12\n";
+        assert_eq!(
+            diff(data, "foo", "bar"),
+            "--- f\n+++ f\n\
+             @@ -1,5 +1,5 @@\n 1\n-2 foo\n+2 bar\n 3\n 4\n 5\n\
+             @@ -7,6 +7,6 @@\n 7\n 8\n 9\n-10 foo\n-11 foo\n+10 bar\n+11 bar\n 12\n"
+        );
+
+        // Context of changes 7 lines apart touches, so they share a hunk
+        let data = "foo\n2\n3\n4\n5\n6\n7\nfoo\n";
+        assert_eq!(
+            diff(data, "foo", "x"),
+            "--- f\n+++ f\n@@ -1,8 +1,8 @@\n-foo\n+x\n 2\n 3\n 4\n 5\n 6\n 7\n-foo\n+x\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_split_lines_and_missing_newline() {
+        let data = "foo\n2\n3\n4\n5\n6\n7\n8\n9\nfoo";
+        assert_eq!(
+            diff(data, "foo", "a\nb"),
+            "--- f\n+++ f\n\
+             @@ -1,4 +1,5 @@\n-foo\n+a\n+b\n 2\n 3\n 4\n\
+             @@ -7,4 +8,5 @@\n 7\n 8\n 9\n-foo\n\\ No newline at end of file\n\
+             +a\n+b\n\\ No newline at end of file\n"
+        );
+    }
+}
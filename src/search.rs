@@ -1,24 +1,172 @@
 //! Search functionality for pattern matching
+//!
+//! Lines are searched as raw bytes, so input that is not valid UTF-8 (for
+//! example Latin-1 or corrupted logs) is still searched and printed as is.
 
 use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::ops::{AddAssign, Range};
+use std::path::Path;
+use std::sync::OnceLock;
 
-use crate::cli::Config;
+use aho_corasick::{AhoCorasick, MatchKind};
+use memchr::memmem;
+use regex::bytes::{Regex, RegexBuilder};
+use regex_syntax::ast::{self, Ast};
+
+use crate::casefold;
+use crate::cli::{ColumnUnit, Config};
+use crate::color::{self, push_styled};
+use crate::errors::RgrepError;
+use crate::io::path_bytes;
 
 /// Result of a line match
 #[derive(Debug)]
 pub struct MatchResult {
-    /// The original line content
-    pub line: String,
+    /// The original line content, without its terminator
+    pub line: Vec<u8>,
     /// Line number (1-based)
     pub line_number: usize,
+    /// Byte offset of the start of the line in its input
+    pub byte_offset: usize,
     /// Whether this line matched the pattern
     pub matched: bool,
+    /// Byte ranges of the non-empty matches within the line
+    pub spans: Vec<Range<usize>>,
+}
+
+impl MatchResult {
+    /// Whether this line is selected for output, honouring inverted matching
+    pub fn is_selected(&self, invert_match: bool) -> bool {
+        self.matched != invert_match
+    }
+}
+
+/// Counts of what was found while searching
+///
+/// Kept per input and summed over the whole search.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Inputs searched
+    pub inputs: usize,
+    /// Inputs with at least one selected line
+    pub matched_inputs: usize,
+    /// Selected lines; output modes that stop at the first one count one
+    pub selected_lines: usize,
+    /// Matches within the selected lines, when they are counted
+    pub matches: usize,
+}
+
+impl SearchStats {
+    /// Statistics for one searched input
+    pub fn for_input(selected_lines: usize, matches: usize) -> Self {
+        Self {
+            inputs: 1,
+            matched_inputs: usize::from(selected_lines > 0),
+            selected_lines,
+            matches,
+        }
+    }
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.inputs += other.inputs;
+        self.matched_inputs += other.matched_inputs;
+        self.selected_lines += other.selected_lines;
+        self.matches += other.matches;
+    }
 }
 
-/// A matcher that can check if a line matches the pattern
-pub trait Matcher {
+/// A matcher that can locate the pattern within a line
+///
+/// Matchers are shared by the search threads, so they must be thread-safe.
+pub trait Matcher: Send + Sync {
+    /// Find the first match starting at or after byte offset `start`
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>>;
+
     /// Check if the given line matches
-    fn matches(&self, line: &str) -> bool;
+    fn matches(&self, line: &[u8]) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// Find the first match in the line
+    fn find(&self, line: &[u8]) -> Option<Range<usize>> {
+        self.find_at(line, 0)
+    }
+
+    /// Find where the first line that may match begins in a block of lines
+    ///
+    /// `haystack` holds whole lines with their terminators and `start` is the
+    /// beginning of one of them. The returned offset lies within the first line that could
+    /// match; it must not skip any line that matches on its own, but it may
+    /// point at lines that turn out not to match. The default treats every
+    /// line as a candidate.
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        (start < haystack.len()).then_some(start)
+    }
+
+    /// Append the replacement for the match at `span` in `line` to `dst`
+    ///
+    /// Matchers with capture groups expand `$1` and `${name}` references in
+    /// `replacement`; the default inserts it unchanged.
+    fn expand_replacement(
+        &self,
+        _line: &[u8],
+        _span: Range<usize>,
+        replacement: &[u8],
+        dst: &mut Vec<u8>,
+    ) {
+        dst.extend_from_slice(replacement);
+    }
+
+    /// Find all non-overlapping, non-empty matches in the line
+    fn find_all(&self, line: &[u8]) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+
+        while start <= line.len() {
+            let Some(span) = self.find_at(line, start) else {
+                break;
+            };
+
+            start = if span.is_empty() {
+                span.end + char_len_at(line, span.end)
+            } else {
+                span.end
+            };
+
+            if !span.is_empty() {
+                spans.push(span);
+            }
+        }
+
+        spans
+    }
+}
+
+/// Decode the UTF-8 character at the start of `bytes`
+///
+/// Returns `None` for an empty slice or an invalid or truncated sequence.
+fn decode_char(bytes: &[u8]) -> Option<(char, usize)> {
+    let len = match *bytes.first()? {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return None,
+    };
+
+    let c = std::str::from_utf8(bytes.get(..len)?)
+        .ok()?
+        .chars()
+        .next()?;
+    Some((c, len))
+}
+
+/// Length of the character at `idx`, counting an invalid byte as one
+fn char_len_at(line: &[u8], idx: usize) -> usize {
+    decode_char(&line[idx..]).map_or(1, |(_, len)| len)
 }
 
 /// Case-sensitive literal matcher
@@ -33,63 +181,890 @@ impl LiteralMatcher {
 }
 
 impl Matcher for LiteralMatcher {
-    fn matches(&self, line: &str) -> bool {
-        line.contains(&self.pattern)
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        memmem::find(&line[start..], self.pattern.as_bytes()).map(|pos| {
+            let begin = start + pos;
+            begin..begin + self.pattern.len()
+        })
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        self.find_at(haystack, start).map(|span| span.start)
     }
 }
 
-/// Case-insensitive literal matcher  
+/// Case-insensitive literal matcher
+///
+/// The Unicode case foldings of the pattern and the line are compared on the
+/// fly, so lines are never copied or lowercased.
 pub struct CaseInsensitiveMatcher {
-    pattern_lower: String,
+    pattern_folded: Vec<char>,
 }
 
 impl CaseInsensitiveMatcher {
     pub fn new(pattern: String) -> Self {
         Self {
-            pattern_lower: pattern.to_lowercase(),
+            pattern_folded: casefold::fold_str(&pattern).collect(),
         }
     }
 }
 
+impl CaseInsensitiveMatcher {
+    /// Length in bytes of the match of the pattern at the start of `text`
+    ///
+    /// A match must cover whole characters of the line, so `s` does not
+    /// match half of `ß`. Invalid UTF-8 never matches, since the pattern
+    /// itself is valid UTF-8.
+    fn match_len_at(&self, text: &[u8]) -> Option<usize> {
+        let mut pattern = self.pattern_folded.iter();
+        let mut consumed = 0;
+
+        while !pattern.as_slice().is_empty() {
+            let (c, len) = decode_char(&text[consumed..])?;
+
+            for folded in casefold::fold(c) {
+                if pattern.next() != Some(&folded) {
+                    return None;
+                }
+            }
+
+            consumed += len;
+        }
+
+        Some(consumed)
+    }
+}
+
 impl Matcher for CaseInsensitiveMatcher {
-    fn matches(&self, line: &str) -> bool {
-        // Use Cow to avoid unnecessary allocations for ASCII-only content
-        let line_lower: Cow<str> = if line.is_ascii() {
-            Cow::Owned(line.to_ascii_lowercase())
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        // Compare in place so byte offsets refer to the original line
+        let first = self.pattern_folded.first().copied();
+        let mut begin = start;
+
+        loop {
+            // ASCII only folds to ASCII, so most bytes are ruled out cheaply
+            if let (Some(&byte), Some(first)) = (line.get(begin), first) {
+                if byte.is_ascii() && char::from(byte.to_ascii_lowercase()) != first {
+                    begin += 1;
+                    continue;
+                }
+            }
+
+            if let Some(len) = self.match_len_at(&line[begin..]) {
+                return Some(begin..begin + len);
+            }
+
+            if begin >= line.len() {
+                return None;
+            }
+
+            begin += char_len_at(line, begin);
+        }
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        self.find_at(haystack, start).map(|span| span.start)
+    }
+}
+
+/// Regular expression matcher
+///
+/// Several patterns are combined into one regex without their capture
+/// groups, so that group numbers and names of different patterns cannot
+/// clash. Replacements are expanded with the pattern that matched, compiled
+/// on its own the first time one is needed.
+pub struct RegexMatcher {
+    regex: Regex,
+    /// Multi-line variant used to scan whole blocks, when that is safe
+    block_regex: Option<Regex>,
+    /// The patterns combined into `regex`, if there are several
+    patterns: Vec<String>,
+    alternatives: OnceLock<Vec<Regex>>,
+    ignore_case: bool,
+    terminator: u8,
+}
+
+impl RegexMatcher {
+    /// Compile the pattern, optionally ignoring case
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Self, RgrepError> {
+        Self::with_patterns(&[pattern], ignore_case)
+    }
+
+    /// Compile a regex matching any of the patterns
+    pub fn with_patterns(patterns: &[&str], ignore_case: bool) -> Result<Self, RgrepError> {
+        Self::with_line_terminator(patterns, ignore_case, b'\n')
+    }
+
+    /// Compile a regex matching any of the patterns, for input whose lines
+    /// end with `terminator`
+    pub fn with_line_terminator(
+        patterns: &[&str],
+        ignore_case: bool,
+        terminator: u8,
+    ) -> Result<Self, RgrepError> {
+        let combined = match patterns {
+            [pattern] => pattern.to_string(),
+            _ => {
+                // Parse each pattern alone so one cannot close another's group
+                let groups = patterns
+                    .iter()
+                    .map(|p| Ok(format!("(?:{})", without_captures(p)?)))
+                    .collect::<Result<Vec<_>, RgrepError>>()?;
+                groups.join("|")
+            }
+        };
+
+        let build = |multi_line| compile(&combined, ignore_case, multi_line, terminator);
+        let regex = build(false)?;
+
+        // In multi-line mode `^` matches at the start of every line, so a
+        // block match exists wherever a line matches. `$` (which would miss
+        // CRLF endings), absolute anchors and inline flags break that;
+        // non-capturing groups do not.
+        let block_safe = !patterns.iter().any(|p| {
+            let p = p.replace("(?:", "");
+            ["$", r"\A", r"\z", "(?"]
+                .iter()
+                .any(|construct| p.contains(construct))
+        });
+        let block_regex = if block_safe { Some(build(true)?) } else { None };
+
+        let patterns = match patterns {
+            [_] => Vec::new(),
+            _ => patterns.iter().map(|p| p.to_string()).collect(),
+        };
+
+        Ok(Self {
+            regex,
+            block_regex,
+            patterns,
+            alternatives: OnceLock::new(),
+            ignore_case,
+            terminator,
+        })
+    }
+
+    /// Each of several patterns compiled on its own, in order
+    ///
+    /// A pattern that does not compile alone is left out; its matches are
+    /// then replaced without expanding references.
+    fn alternatives(&self) -> &[Regex] {
+        self.alternatives.get_or_init(|| {
+            self.patterns
+                .iter()
+                .filter_map(|p| compile(p, self.ignore_case, false, self.terminator).ok())
+                .collect()
+        })
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        self.regex.find_at(line, start).map(|m| m.range())
+    }
+
+    fn matches(&self, line: &[u8]) -> bool {
+        self.regex.is_match(line)
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        match &self.block_regex {
+            Some(regex) => regex.find_at(haystack, start).map(|m| m.start()),
+            None => (start < haystack.len()).then_some(start),
+        }
+    }
+
+    fn expand_replacement(
+        &self,
+        line: &[u8],
+        span: Range<usize>,
+        replacement: &[u8],
+        dst: &mut Vec<u8>,
+    ) {
+        let captures = if self.patterns.is_empty() {
+            self.regex.captures_at(line, span.start)
+        } else {
+            // Like the combined regex, prefer the first pattern matching here
+            self.alternatives().iter().find_map(|regex| {
+                regex
+                    .captures_at(line, span.start)
+                    .filter(|captures| captures.get(0).map(|m| m.range()) == Some(span.clone()))
+            })
+        };
+
+        match captures {
+            Some(captures) => captures.expand(replacement, dst),
+            None => dst.extend_from_slice(replacement),
+        }
+    }
+}
+
+/// Compile a regex with the search's settings
+fn compile(
+    pattern: &str,
+    ignore_case: bool,
+    multi_line: bool,
+    terminator: u8,
+) -> Result<Regex, RgrepError> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .multi_line(multi_line)
+        .line_terminator(terminator)
+        .build()
+        .map_err(|e| invalid_regex(&e))
+}
+
+/// Rewrite a regex with its capture groups made non-capturing
+fn without_captures(pattern: &str) -> Result<String, RgrepError> {
+    let mut ast = ast::parse::Parser::new()
+        .parse(pattern)
+        .map_err(|e| invalid_regex(&e))?;
+    strip_captures(&mut ast);
+
+    let mut output = String::new();
+    ast::print::Printer::new()
+        .print(&ast, &mut output)
+        .expect("printing to a string cannot fail");
+    Ok(output)
+}
+
+fn strip_captures(node: &mut Ast) {
+    match node {
+        Ast::Group(group) => {
+            if !matches!(group.kind, ast::GroupKind::NonCapturing(_)) {
+                group.kind = ast::GroupKind::NonCapturing(ast::Flags {
+                    span: group.span,
+                    items: Vec::new(),
+                });
+            }
+            strip_captures(&mut group.ast);
+        }
+        Ast::Repetition(repetition) => strip_captures(&mut repetition.ast),
+        Ast::Alternation(alternation) => alternation.asts.iter_mut().for_each(strip_captures),
+        Ast::Concat(concat) => concat.asts.iter_mut().for_each(strip_captures),
+        _ => {}
+    }
+}
+
+/// Error for a pattern that is not a valid regex
+fn invalid_regex(error: &dyn std::fmt::Display) -> RgrepError {
+    RgrepError::invalid_args(format!("Invalid regex: {error}"), false)
+}
+
+/// Check the syntax of a regex before it is embedded in a larger one
+fn check_regex(pattern: &str) -> Result<(), RgrepError> {
+    ast::parse::Parser::new()
+        .parse(pattern)
+        .map(|_| ())
+        .map_err(|e| invalid_regex(&e))
+}
+
+/// Matcher for many literal patterns at once
+///
+/// Uses an Aho-Corasick automaton, so a search costs about the same however
+/// many patterns there are. Ignoring case, the automaton is built from the
+/// case-folded patterns: ASCII text is searched as it is, with the
+/// automaton's ASCII case folding, and other text is first folded into a
+/// copy (see [`FoldedText`]).
+pub struct MultiLiteralMatcher {
+    automaton: AhoCorasick,
+    /// Per-pattern matchers, to try shorter patterns where the longest one
+    /// ends inside a folded character; empty unless ignoring case
+    folded: Vec<CaseInsensitiveMatcher>,
+    /// Most bytes of text a match can cover
+    max_match_len: usize,
+}
+
+/// Bytes of a block folded at a time by [`MultiLiteralMatcher::find_candidate`]
+const FOLD_WINDOW: usize = 64 * 1024;
+
+impl MultiLiteralMatcher {
+    /// Build the automaton, preferring the longest of matches that start together
+    pub fn new(patterns: &[&str], ignore_case: bool) -> Result<Self, RgrepError> {
+        let folded: Vec<CaseInsensitiveMatcher> = if ignore_case {
+            patterns
+                .iter()
+                .map(|p| CaseInsensitiveMatcher::new(p.to_string()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let keys: Vec<String> = if ignore_case {
+            folded
+                .iter()
+                .map(|m| m.pattern_folded.iter().collect())
+                .collect()
+        } else {
+            patterns.iter().map(|p| p.to_string()).collect()
+        };
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(ignore_case)
+            .build(&keys)
+            .map_err(|e| RgrepError::invalid_args(format!("Too many patterns: {e}"), false))?;
+
+        // Every character of the text folds to at least one character, and
+        // is at most four bytes long
+        let max_match_len = if ignore_case {
+            folded.iter().map(|m| 4 * m.pattern_folded.len()).max()
         } else {
-            Cow::Owned(line.to_lowercase())
+            patterns.iter().map(|p| p.len()).max()
         };
 
-        line_lower.contains(&self.pattern_lower)
+        Ok(Self {
+            automaton,
+            folded,
+            max_match_len: max_match_len.unwrap_or(0),
+        })
+    }
+
+    /// Whether `text` must be folded before searching it
+    fn needs_folding(&self, text: &[u8]) -> bool {
+        !self.folded.is_empty() && !text.is_ascii()
+    }
+
+    /// Find the first match in `line` from byte `from` of its folding
+    ///
+    /// Returns the span in `line` and the end of the match in `folded`.
+    fn find_folded(
+        &self,
+        line: &[u8],
+        folded: &FoldedText,
+        mut from: usize,
+    ) -> Option<(Range<usize>, usize)> {
+        loop {
+            let input = aho_corasick::Input::new(&folded.bytes).span(from..folded.bytes.len());
+            let found = self.automaton.find(input)?;
+            from = found.start() + 1;
+
+            // A match must cover whole characters of the line, so `s` does
+            // not match half of `ß`
+            let Some(begin) = folded.origin(found.start()) else {
+                continue;
+            };
+            if let Some(end) = folded.origin(found.end()) {
+                return Some((begin..end, found.end()));
+            }
+
+            // The longest pattern ends inside a character, a shorter one may not
+            if let Some(len) = self
+                .folded
+                .iter()
+                .filter_map(|m| m.match_len_at(&line[begin..]))
+                .max()
+            {
+                let end = folded.position(begin + len, found.start());
+                return Some((begin..begin + len, end));
+            }
+        }
+    }
+}
+
+impl Matcher for MultiLiteralMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        if self.needs_folding(&line[start..]) {
+            let folded = FoldedText::new(line, start..line.len());
+            return self.find_folded(line, &folded, 0).map(|(span, _)| span);
+        }
+
+        let input = aho_corasick::Input::new(line).span(start..line.len());
+        self.automaton.find(input).map(|m| m.range())
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        if self.folded.is_empty() {
+            return self.find_at(haystack, start).map(|span| span.start);
+        }
+
+        // Fold a window at a time, each overlapping the last by the longest
+        // match, so that a block is folded only as far as the first match
+        let mut from = start;
+        while from < haystack.len() {
+            let mut end = (from + FOLD_WINDOW).min(haystack.len());
+            while !is_char_boundary(haystack, end) {
+                end += 1;
+            }
+
+            if !self.needs_folding(&haystack[from..end]) {
+                let input = aho_corasick::Input::new(haystack).span(from..end);
+                if let Some(found) = self.automaton.find(input) {
+                    return Some(found.start());
+                }
+            } else {
+                let folded = FoldedText::new(haystack, from..end);
+                if let Some(found) = self.automaton.find(&folded.bytes) {
+                    return Some(folded.char_start(found.start()));
+                }
+            }
+
+            if end == haystack.len() {
+                break;
+            }
+            let mut next = end.saturating_sub(self.max_match_len).max(from + 1);
+            while !is_char_boundary(haystack, next) {
+                next -= 1;
+            }
+            from = next.max(from + 1);
+        }
+
+        None
+    }
+
+    fn find_all(&self, line: &[u8]) -> Vec<Range<usize>> {
+        if !self.needs_folding(line) {
+            return self
+                .automaton
+                .find_iter(line)
+                .map(|m| m.range())
+                .filter(|span| !span.is_empty())
+                .collect();
+        }
+
+        // Fold the line once rather than for every match
+        let folded = FoldedText::new(line, 0..line.len());
+        let mut spans = Vec::new();
+        let mut from = 0;
+
+        while from <= folded.bytes.len() {
+            let Some((span, end)) = self.find_folded(line, &folded, from) else {
+                break;
+            };
+            from = if span.is_empty() { end + 1 } else { end };
+            if !span.is_empty() {
+                spans.push(span);
+            }
+        }
+
+        spans
+    }
+}
+
+/// The case folding of part of a text, mapped back to the text
+///
+/// Invalid UTF-8 folds to `0xFF`, which no pattern contains.
+struct FoldedText {
+    bytes: Vec<u8>,
+    /// For each byte of `bytes` and its end, the offset in the text of the
+    /// character whose folding starts there, or [`INSIDE_CHAR`]
+    origins: Vec<usize>,
+}
+
+/// Origin of a folded byte that does not start the folding of a character
+const INSIDE_CHAR: usize = usize::MAX;
+
+impl FoldedText {
+    fn new(text: &[u8], range: Range<usize>) -> Self {
+        let mut bytes = Vec::with_capacity(range.len());
+        let mut origins = Vec::with_capacity(range.len() + 1);
+        let mut pos = range.start;
+
+        while pos < range.end {
+            origins.push(pos);
+            match decode_char(&text[pos..range.end]) {
+                Some((c, len)) => {
+                    for folded in casefold::fold(c) {
+                        let mut buf = [0; 4];
+                        bytes.extend_from_slice(folded.encode_utf8(&mut buf).as_bytes());
+                    }
+                    origins.resize(bytes.len(), INSIDE_CHAR);
+                    pos += len;
+                }
+                None => {
+                    bytes.push(0xFF);
+                    pos += 1;
+                }
+            }
+        }
+        origins.push(range.end);
+
+        Self { bytes, origins }
+    }
+
+    /// Offset in the text of the character starting at folded byte `index`
+    fn origin(&self, index: usize) -> Option<usize> {
+        Some(self.origins[index]).filter(|&origin| origin != INSIDE_CHAR)
+    }
+
+    /// Offset in the text of the character folded into byte `index`
+    fn char_start(&self, index: usize) -> usize {
+        self.origins[..=index]
+            .iter()
+            .rev()
+            .find(|&&origin| origin != INSIDE_CHAR)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Folded byte where the character at `offset` of the text starts,
+    /// searching from folded byte `from`
+    fn position(&self, offset: usize, from: usize) -> usize {
+        from + self.origins[from..]
+            .iter()
+            .position(|&origin| origin == offset)
+            .expect("offset is a character boundary")
+    }
+}
+
+/// Whether `idx` is not inside a UTF-8 sequence
+fn is_char_boundary(text: &[u8], idx: usize) -> bool {
+    text.get(idx).is_none_or(|&byte| byte & 0xC0 != 0x80)
+}
+
+/// Matcher selecting the leftmost match of any of several matchers
+pub struct AnyMatcher {
+    matchers: Vec<Box<dyn Matcher>>,
+}
+
+impl AnyMatcher {
+    pub fn new(matchers: Vec<Box<dyn Matcher>>) -> Self {
+        Self { matchers }
+    }
+}
+
+impl Matcher for AnyMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        self.matchers
+            .iter()
+            .filter_map(|m| m.find_at(line, start))
+            .min_by_key(|span| (span.start, Reverse(span.end)))
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        self.matchers
+            .iter()
+            .filter_map(|m| m.find_candidate(haystack, start))
+            .min()
+    }
+}
+
+/// Matcher accepting only matches bounded by non-word characters (`-w`)
+///
+/// When a match fails the boundary test, later matches are tried, so `id`
+/// is found in `width id` even though the first occurrence is inside a word.
+/// Matches of another length at the same start are only tried with
+/// [`WordMatcher::with_alternatives`], so regexes have the boundaries
+/// compiled into the pattern instead.
+pub struct WordMatcher {
+    inner: Box<dyn Matcher>,
+    /// Matchers of the single patterns combined in `inner`
+    alternatives: Vec<Box<dyn Matcher>>,
+}
+
+impl WordMatcher {
+    pub fn new(inner: Box<dyn Matcher>) -> Self {
+        Self {
+            inner,
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Try each of `alternatives` where the match of `inner` fails the
+    /// boundary test, so `foo` is found in `foo barx` with `foo bar`
+    ///
+    /// `inner` must prefer the longest of the matches starting together.
+    pub fn with_alternatives(mut self, alternatives: Vec<Box<dyn Matcher>>) -> Self {
+        self.alternatives = alternatives;
+        self
+    }
+
+    /// The longest match of the alternatives at the start of `span`, which
+    /// is no longer than `span`, that passes the boundary test
+    fn shorter_match(&self, line: &[u8], span: &Range<usize>) -> Option<Range<usize>> {
+        self.alternatives
+            .iter()
+            .filter_map(|m| m.find_at(&line[..span.end], span.start))
+            .filter(|shorter| shorter.start == span.start && is_whole_word(line, shorter))
+            .max_by_key(|shorter| shorter.end)
     }
 }
 
+impl Matcher for WordMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        let mut from = start;
+
+        while from <= line.len() {
+            let span = self.inner.find_at(line, from)?;
+
+            if is_whole_word(line, &span) {
+                return Some(span);
+            }
+            if let Some(shorter) = self.shorter_match(line, &span) {
+                return Some(shorter);
+            }
+
+            from = span.start + char_len_at(line, span.start);
+        }
+
+        None
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        self.inner.find_candidate(haystack, start)
+    }
+
+    fn expand_replacement(
+        &self,
+        line: &[u8],
+        span: Range<usize>,
+        replacement: &[u8],
+        dst: &mut Vec<u8>,
+    ) {
+        self.inner.expand_replacement(line, span, replacement, dst);
+    }
+}
+
+/// Matcher accepting only a match of the whole line (`-x`)
+pub struct LineMatcher {
+    inner: Box<dyn Matcher>,
+}
+
+impl LineMatcher {
+    pub fn new(inner: Box<dyn Matcher>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Matcher for LineMatcher {
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        if start > 0 {
+            return None;
+        }
+
+        self.inner
+            .find_at(line, 0)
+            .filter(|span| *span == (0..line.len()))
+    }
+
+    fn find_candidate(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        self.inner.find_candidate(haystack, start)
+    }
+
+    fn expand_replacement(
+        &self,
+        line: &[u8],
+        span: Range<usize>,
+        replacement: &[u8],
+        dst: &mut Vec<u8>,
+    ) {
+        self.inner.expand_replacement(line, span, replacement, dst);
+    }
+}
+
+/// Whether `span` is not empty and not next to a word character
+fn is_whole_word(line: &[u8], span: &Range<usize>) -> bool {
+    let before = char_before(line, span.start);
+    let after = decode_char(&line[span.end..]).map(|(c, _)| c);
+    !span.is_empty() && !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+/// Word characters for `-w`: letters, digits and underscore
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Decode the character ending just before byte offset `idx`
+fn char_before(line: &[u8], idx: usize) -> Option<char> {
+    let start = idx.saturating_sub(4);
+    (start..idx)
+        .filter_map(|i| decode_char(&line[i..idx]).filter(|&(_, len)| i + len == idx))
+        .map(|(c, _)| c)
+        .next()
+}
+
 /// Create the appropriate matcher based on configuration
-pub fn create_matcher(config: &Config) -> Box<dyn Matcher> {
-    if config.ignore_case {
-        Box::new(CaseInsensitiveMatcher::new(config.pattern.clone()))
+pub fn create_matcher(config: &Config) -> Result<Box<dyn Matcher>, RgrepError> {
+    let patterns: Vec<&str> = config.all_patterns().collect();
+    let ignore_case = config.ignore_case
+        || (config.smart_case && !patterns.iter().any(|p| has_uppercase(p, config.regex)));
+    let matcher = create_pattern_matcher(config, &patterns, ignore_case)?;
+
+    if config.line_regexp {
+        Ok(Box::new(LineMatcher::new(matcher)))
+    } else if config.word_regexp && !config.regex {
+        // Several literals may match at one start, and only a shorter match
+        // may be a whole word
+        let alternatives = match patterns[..] {
+            [_] => Vec::new(),
+            _ => patterns
+                .iter()
+                .map(|p| literal_matcher(p, ignore_case))
+                .collect(),
+        };
+        Ok(Box::new(
+            WordMatcher::new(matcher).with_alternatives(alternatives),
+        ))
     } else {
-        Box::new(LiteralMatcher::new(config.pattern.clone()))
+        Ok(matcher)
+    }
+}
+
+/// Create the matcher for the patterns themselves, ignoring `-w` and `-x`
+fn create_pattern_matcher(
+    config: &Config,
+    patterns: &[&str],
+    ignore_case: bool,
+) -> Result<Box<dyn Matcher>, RgrepError> {
+    if patterns.is_empty() {
+        // Nothing matches without patterns (an empty `-f` file)
+        Ok(Box::new(AnyMatcher::new(Vec::new())))
+    } else if config.regex && (config.line_regexp || config.word_regexp) {
+        // Anchor at the line ends or word boundaries, so that alternations
+        // prefer a match that satisfies them over a shorter alternative
+        let (prefix, suffix) = if config.line_regexp {
+            ("^", "$")
+        } else {
+            (r"\b{start-half}", r"\b{end-half}")
+        };
+        for pattern in patterns {
+            check_regex(pattern)?;
+        }
+        let anchored: Vec<String> = patterns
+            .iter()
+            .map(|p| format!("{prefix}(?:{p}){suffix}"))
+            .collect();
+        let anchored: Vec<&str> = anchored.iter().map(String::as_str).collect();
+        Ok(Box::new(RegexMatcher::with_line_terminator(
+            &anchored,
+            ignore_case,
+            config.line_terminator(),
+        )?))
+    } else if config.regex {
+        Ok(Box::new(RegexMatcher::with_line_terminator(
+            patterns,
+            ignore_case,
+            config.line_terminator(),
+        )?))
+    } else if let [pattern] = patterns[..] {
+        Ok(literal_matcher(pattern, ignore_case))
+    } else {
+        Ok(Box::new(MultiLiteralMatcher::new(patterns, ignore_case)?))
+    }
+}
+
+/// Create the matcher for a single literal pattern
+fn literal_matcher(pattern: &str, ignore_case: bool) -> Box<dyn Matcher> {
+    if ignore_case {
+        Box::new(CaseInsensitiveMatcher::new(pattern.to_string()))
+    } else {
+        Box::new(LiteralMatcher::new(pattern.to_string()))
+    }
+}
+
+/// Check whether a pattern contains an uppercase letter, for smart case
+///
+/// In regular expressions only literal characters count, so escapes such as
+/// `\S` or `\p{Lu}` keep the search case-insensitive. A regex that does not
+/// parse is checked as plain text; compiling it reports the error.
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    if regex {
+        if let Ok(ast) = ast::parse::Parser::new().parse(pattern) {
+            return ast::visit(&ast, UppercaseLiterals).is_err();
+        }
+    }
+
+    pattern.chars().any(char::is_uppercase)
+}
+
+/// Regex AST visitor that stops with an error at the first uppercase literal
+struct UppercaseLiterals;
+
+impl ast::Visitor for UppercaseLiterals {
+    type Output = ();
+    type Err = ();
+
+    fn finish(self) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), ()> {
+        match ast {
+            Ast::Literal(literal) if literal.c.is_uppercase() => Err(()),
+            _ => Ok(()),
+        }
+    }
+
+    fn visit_class_set_item_pre(&mut self, item: &ast::ClassSetItem) -> Result<(), ()> {
+        let uppercase = match item {
+            ast::ClassSetItem::Literal(literal) => literal.c.is_uppercase(),
+            ast::ClassSetItem::Range(range) => {
+                range.start.c.is_uppercase() || range.end.c.is_uppercase()
+            }
+            _ => false,
+        };
+
+        if uppercase {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Rewrite each matched span of a line with its replacement
+///
+/// The spans of the result cover the inserted replacements, so they are
+/// highlighted and printed by `-o` like the original matches were.
+pub fn replace_matches(
+    match_result: MatchResult,
+    matcher: &dyn Matcher,
+    replacement: &[u8],
+) -> MatchResult {
+    if match_result.spans.is_empty() {
+        return match_result;
+    }
+
+    let line = &match_result.line;
+    let mut replaced = Vec::with_capacity(line.len());
+    let mut spans = Vec::with_capacity(match_result.spans.len());
+    let mut last = 0;
+
+    for span in &match_result.spans {
+        replaced.extend_from_slice(&line[last..span.start]);
+        let start = replaced.len();
+        matcher.expand_replacement(line, span.clone(), replacement, &mut replaced);
+        spans.push(start..replaced.len());
+        last = span.end;
+    }
+    replaced.extend_from_slice(&line[last..]);
+
+    MatchResult {
+        line: replaced,
+        spans,
+        ..match_result
     }
 }
 
 /// Search through lines and yield match results
+///
+/// Byte offsets assume each line was terminated by a single `\n`.
 pub fn search_lines<I>(
     lines: I,
     matcher: &dyn Matcher,
 ) -> impl Iterator<Item = MatchResult> + use<'_, I>
 where
-    I: Iterator<Item = Result<String, std::io::Error>>,
+    I: Iterator<Item = Result<Vec<u8>, std::io::Error>>,
 {
-    lines.enumerate().filter_map(|(idx, line_result)| {
+    let mut byte_offset = 0;
+
+    lines.enumerate().filter_map(move |(idx, line_result)| {
         match line_result {
             Ok(line) => {
+                let line_offset = byte_offset;
+                byte_offset += line.len() + 1;
                 let line_number = idx + 1;
                 let matched = matcher.matches(&line);
+                let spans = if matched {
+                    matcher.find_all(&line)
+                } else {
+                    Vec::new()
+                };
                 Some(MatchResult {
                     line,
                     line_number,
+                    byte_offset: line_offset,
                     matched,
+                    spans,
                 })
             }
             Err(_) => None, // Skip lines that couldn't be read
@@ -97,31 +1072,267 @@ where
     })
 }
 
+/// Options controlling how a match is rendered
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    /// Prefix each line with the source name
+    pub show_filename: bool,
+    /// Prefix each line with its line number
+    pub show_line_numbers: bool,
+    /// Prefix each line with the column of its first match
+    pub show_column: Option<ColumnUnit>,
+    /// Prefix each line with its byte offset in the input
+    pub show_byte_offset: bool,
+    /// Follow file names with a NUL byte instead of a separator
+    pub null: bool,
+    /// Emit ANSI colors for prefixes and matched spans
+    pub color: bool,
+}
+
 /// Format output for a match
 pub fn format_match(
     match_result: &MatchResult,
-    source_name: Option<&str>,
+    source_name: Option<&Path>,
     show_filename: bool,
     show_line_numbers: bool,
-) -> String {
-    let mut output = String::new();
+) -> Vec<u8> {
+    let options = OutputOptions {
+        show_filename,
+        show_line_numbers,
+        ..OutputOptions::default()
+    };
+
+    format_match_with(match_result, source_name, &options)
+}
+
+/// Format output for a match using the given output options
+pub fn format_match_with(
+    match_result: &MatchResult,
+    source_name: Option<&Path>,
+    options: &OutputOptions,
+) -> Vec<u8> {
+    format_line(match_result, source_name, options, ":")
+}
+
+/// Format a context line, using `-` instead of `:` after the prefixes
+pub fn format_context(
+    match_result: &MatchResult,
+    source_name: Option<&Path>,
+    options: &OutputOptions,
+) -> Vec<u8> {
+    format_line(match_result, source_name, options, "-")
+}
+
+/// Format the separator printed between non-adjacent context groups
+pub fn format_group_separator(options: &OutputOptions) -> Vec<u8> {
+    let mut output = Vec::new();
+    push_styled(&mut output, b"--", color::SEPARATOR_STYLE, options.color);
+    output
+}
+
+/// Format each matched span of a line on its own, for `-o`
+///
+/// Every span gets the same prefixes as a whole line would. Empty matches
+/// are skipped, as they would print only the prefixes.
+pub fn format_only_matching(
+    match_result: &MatchResult,
+    source_name: Option<&Path>,
+    options: &OutputOptions,
+) -> Vec<Vec<u8>> {
+    match_result
+        .spans
+        .iter()
+        .filter(|span| !span.is_empty())
+        .map(|span| {
+            let mut output = Vec::new();
+            let position = MatchPosition {
+                line_number: match_result.line_number,
+                column: options
+                    .show_column
+                    .map(|unit| column_number(&match_result.line, span.start, unit)),
+                byte_offset: match_result.byte_offset + span.start,
+            };
+            push_prefixes(&mut output, &position, source_name, options, ":");
+            push_styled(
+                &mut output,
+                &match_result.line[span.clone()],
+                color::MATCH_STYLE,
+                options.color,
+            );
+            output
+        })
+        .collect()
+}
+
+/// Format a line with its prefixes joined by `separator`
+fn format_line(
+    match_result: &MatchResult,
+    source_name: Option<&Path>,
+    options: &OutputOptions,
+    separator: &str,
+) -> Vec<u8> {
+    let mut output = Vec::new();
+    let position = MatchPosition {
+        line_number: match_result.line_number,
+        column: options
+            .show_column
+            .zip(match_result.spans.first())
+            .map(|(unit, span)| column_number(&match_result.line, span.start, unit)),
+        byte_offset: match_result.byte_offset,
+    };
+    push_prefixes(&mut output, &position, source_name, options, separator);
+
+    // Add the actual line content, highlighting matched spans
+    if options.color {
+        let line = &match_result.line;
+        let mut last = 0;
+
+        for span in &match_result.spans {
+            output.extend_from_slice(&line[last..span.start]);
+            push_styled(&mut output, &line[span.clone()], color::MATCH_STYLE, true);
+            last = span.end;
+        }
+
+        output.extend_from_slice(&line[last..]);
+    } else {
+        output.extend_from_slice(&match_result.line);
+    }
+
+    output
+}
+
+/// Where a printed line or match was found
+struct MatchPosition {
+    line_number: usize,
+    /// Column of the match, if there is one
+    column: Option<usize>,
+    byte_offset: usize,
+}
+
+/// The 1-based column of byte offset `start` within `line`
+///
+/// In characters, each byte that is not part of a valid UTF-8 sequence
+/// counts as one column.
+pub fn column_number(line: &[u8], start: usize, unit: ColumnUnit) -> usize {
+    match unit {
+        ColumnUnit::Chars => {
+            let mut column = 1;
+            let mut idx = 0;
+            while idx < start {
+                idx += char_len_at(line, idx);
+                column += 1;
+            }
+            column
+        }
+        ColumnUnit::Bytes => start + 1,
+    }
+}
 
+/// Append the enabled filename, line number, column and byte offset
+/// prefixes, each followed by `separator`
+fn push_prefixes(
+    output: &mut Vec<u8>,
+    position: &MatchPosition,
+    source_name: Option<&Path>,
+    options: &OutputOptions,
+    separator: &str,
+) {
     // Add filename prefix if needed
-    if show_filename {
+    if options.show_filename {
         if let Some(name) = source_name {
-            output.push_str(name);
-            output.push(':');
+            push_styled(
+                output,
+                &path_bytes(name),
+                color::FILENAME_STYLE,
+                options.color,
+            );
+            push_filename_separator(output, separator, options);
+        }
+    }
+
+    // Add line number, column and byte offset if needed
+    let numbers = [
+        (options.show_line_numbers, Some(position.line_number)),
+        (options.show_column.is_some(), position.column),
+        (options.show_byte_offset, Some(position.byte_offset)),
+    ];
+    for (shown, number) in numbers {
+        if let (true, Some(number)) = (shown, number) {
+            push_styled(
+                output,
+                number.to_string().as_bytes(),
+                color::LINE_NUMBER_STYLE,
+                options.color,
+            );
+            push_styled(
+                output,
+                separator.as_bytes(),
+                color::SEPARATOR_STYLE,
+                options.color,
+            );
         }
     }
+}
 
-    // Add line number if needed
-    if show_line_numbers {
-        output.push_str(&format!("{}:", match_result.line_number));
+/// Append what follows a file name: `separator`, or NUL with `-Z`
+fn push_filename_separator(output: &mut Vec<u8>, separator: &str, options: &OutputOptions) {
+    if options.null {
+        output.push(b'\0');
+    } else {
+        push_styled(
+            output,
+            separator.as_bytes(),
+            color::SEPARATOR_STYLE,
+            options.color,
+        );
     }
+}
+
+/// Name shown for standard input in file-oriented output
+pub const STDIN_LABEL: &str = "(standard input)";
+
+/// Name of a source in output, with standard input labelled
+fn display_name(source_name: Option<&Path>) -> Cow<'_, [u8]> {
+    source_name.map_or(Cow::Borrowed(STDIN_LABEL.as_bytes()), path_bytes)
+}
 
-    // Add the actual line content
-    output.push_str(&match_result.line);
+/// Format the count of selected lines for a source
+pub fn format_count(count: usize, source_name: Option<&Path>, options: &OutputOptions) -> Vec<u8> {
+    let mut output = Vec::new();
 
+    if options.show_filename {
+        if let Some(name) = source_name {
+            push_styled(
+                &mut output,
+                &path_bytes(name),
+                color::FILENAME_STYLE,
+                options.color,
+            );
+            push_filename_separator(&mut output, ":", options);
+        }
+    }
+
+    output.extend_from_slice(count.to_string().as_bytes());
+    output
+}
+
+/// Format the notice printed instead of lines for a matching binary source
+pub fn format_binary_match(source_name: Option<&Path>) -> Vec<u8> {
+    let mut output = b"Binary file ".to_vec();
+    output.extend_from_slice(&display_name(source_name));
+    output.extend_from_slice(b" matches");
+    output
+}
+
+/// Format a source name for files-with(out)-matches output
+pub fn format_filename(source_name: Option<&Path>, options: &OutputOptions) -> Vec<u8> {
+    let mut output = Vec::new();
+    push_styled(
+        &mut output,
+        &display_name(source_name),
+        color::FILENAME_STYLE,
+        options.color,
+    );
     output
 }
 
@@ -132,51 +1343,327 @@ mod tests {
     #[test]
     fn test_literal_matcher() {
         let matcher = LiteralMatcher::new("test".to_string());
-        assert!(matcher.matches("this is a test"));
-        assert!(matcher.matches("testing"));
-        assert!(!matcher.matches("this is a TEST"));
-        assert!(!matcher.matches("no match here"));
+        assert!(matcher.matches(b"this is a test"));
+        assert!(matcher.matches(b"testing"));
+        assert!(!matcher.matches(b"this is a TEST"));
+        assert!(!matcher.matches(b"no match here"));
     }
 
     #[test]
     fn test_case_insensitive_matcher() {
         let matcher = CaseInsensitiveMatcher::new("Test".to_string());
-        assert!(matcher.matches("this is a test"));
-        assert!(matcher.matches("this is a TEST"));
-        assert!(matcher.matches("Testing"));
-        assert!(!matcher.matches("no match here"));
+        assert!(matcher.matches(b"this is a test"));
+        assert!(matcher.matches(b"this is a TEST"));
+        assert!(matcher.matches(b"Testing"));
+        assert!(!matcher.matches(b"no match here"));
+    }
+
+    #[test]
+    fn test_literal_matcher_spans() {
+        let matcher = LiteralMatcher::new("ab".to_string());
+        assert_eq!(matcher.find(b"xxabyab"), Some(2..4));
+        assert_eq!(matcher.find_at(b"xxabyab", 3), Some(5..7));
+        assert_eq!(matcher.find_all(b"xxabyab"), vec![2..4, 5..7]);
+        assert_eq!(matcher.find_all(b"aaa"), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn test_case_insensitive_matcher_spans() {
+        let matcher = CaseInsensitiveMatcher::new("straße".to_string());
+        let line = "Die STRASSE, die Straße, die STRAẞE";
+        let spans = matcher.find_all(line.as_bytes());
+        assert_eq!(spans.len(), 3);
+        assert_eq!(&line[spans[0].clone()], "STRASSE");
+        assert_eq!(&line[spans[1].clone()], "Straße");
+        assert_eq!(&line[spans[2].clone()], "STRAẞE");
+
+        // Matches cover whole characters
+        let matcher = CaseInsensitiveMatcher::new("s".to_string());
+        assert!(!matcher.matches("ß".as_bytes()));
+        let matcher = CaseInsensitiveMatcher::new("ss".to_string());
+        assert_eq!(matcher.find_all("aßb".as_bytes()), vec![1..3]);
+    }
+
+    #[test]
+    fn test_matchers_on_invalid_utf8() {
+        let line = b"\xff caf\xe9 CAF\xc3\xa9 \xe2\x82";
+
+        let matcher = LiteralMatcher::new("caf".to_string());
+        assert_eq!(matcher.find_all(line), vec![2..5]);
+
+        let matcher = CaseInsensitiveMatcher::new("café".to_string());
+        assert_eq!(matcher.find_all(line), vec![7..12]);
+
+        let matcher = RegexMatcher::new(r"(?i)caf.", false).unwrap();
+        assert_eq!(matcher.find_all(line), vec![7..12]);
+
+        // Empty matches step over invalid and truncated sequences
+        let matcher = RegexMatcher::new("x*", false).unwrap();
+        assert!(matcher.find_all(line).is_empty());
+    }
+
+    #[test]
+    fn test_multi_literal_matcher_folds_text() {
+        let matcher = MultiLiteralMatcher::new(&["xs", "x", "café"], true).unwrap();
+        let line = [b"\xff ".as_slice(), "Xß CAFÉ xß".as_bytes()].concat();
+
+        // `xs` would end inside `ß`, so the shorter `x` is taken
+        assert_eq!(matcher.find_at(&line, 0), Some(2..3));
+        assert_eq!(matcher.find_all(&line), vec![2..3, 6..11, 12..13]);
+        let matcher = MultiLiteralMatcher::new(&["s", "é"], true).unwrap();
+        assert_eq!(matcher.find(&line), Some(9..11));
+
+        // A match straddling two folded windows is still a candidate
+        let mut block = "é".repeat(FOLD_WINDOW / 2 - 1).into_bytes();
+        block.extend_from_slice("CAFÉ\n".as_bytes());
+        let matcher = MultiLiteralMatcher::new(&["xs", "café"], true).unwrap();
+        assert_eq!(matcher.find_candidate(&block, 0), Some(FOLD_WINDOW - 2));
+        assert_eq!(matcher.find_candidate(&block, FOLD_WINDOW), None);
+    }
+
+    #[test]
+    fn test_regex_matcher_spans() {
+        let matcher = RegexMatcher::new(r"\d+", false).unwrap();
+        assert_eq!(matcher.find_all(b"a1 b22 c333"), vec![1..2, 4..6, 8..11]);
+
+        // Empty matches are skipped without looping forever
+        let matcher = RegexMatcher::new("x*", false).unwrap();
+        assert_eq!(matcher.find_all(b"axxb"), vec![1..3]);
+    }
+
+    #[test]
+    fn test_format_match_with_color() {
+        let match_result = MatchResult {
+            line: b"a foo b foo".to_vec(),
+            line_number: 3,
+            byte_offset: 0,
+            matched: true,
+            spans: vec![2..5, 8..11],
+        };
+        let options = OutputOptions {
+            show_filename: true,
+            show_line_numbers: true,
+            color: true,
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            format_match_with(&match_result, Some(Path::new("f.txt")), &options),
+            b"\x1b[35mf.txt\x1b[0m\x1b[36m:\x1b[0m\x1b[32m3\x1b[0m\x1b[36m:\x1b[0m\
+             a \x1b[1;31mfoo\x1b[0m b \x1b[1;31mfoo\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_format_context() {
+        let match_result = MatchResult {
+            line: b"around".to_vec(),
+            line_number: 7,
+            byte_offset: 0,
+            matched: false,
+            spans: Vec::new(),
+        };
+        let options = OutputOptions {
+            show_filename: true,
+            show_line_numbers: true,
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            format_context(&match_result, Some(Path::new("a.txt")), &options),
+            b"a.txt-7-around"
+        );
+        assert_eq!(format_group_separator(&options), b"--");
+    }
+
+    #[test]
+    fn test_format_only_matching() {
+        let match_result = MatchResult {
+            line: b"id=7 id= id=42".to_vec(),
+            line_number: 3,
+            byte_offset: 0,
+            matched: true,
+            spans: vec![0..4, 5..5, 9..14],
+        };
+        let options = OutputOptions {
+            show_filename: true,
+            show_line_numbers: true,
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            format_only_matching(&match_result, Some(Path::new("a.log")), &options),
+            vec![b"a.log:3:id=7".to_vec(), b"a.log:3:id=42".to_vec()]
+        );
+
+        let colored = OutputOptions {
+            color: true,
+            ..OutputOptions::default()
+        };
+        assert_eq!(
+            format_only_matching(&match_result, None, &colored)[0],
+            format!("{}id=7{}", color::MATCH_STYLE, color::RESET).into_bytes()
+        );
+    }
+
+    #[test]
+    fn test_format_column_and_byte_offset() {
+        let match_result = MatchResult {
+            line: "façade foo foo".as_bytes().to_vec(),
+            line_number: 2,
+            byte_offset: 40,
+            matched: true,
+            spans: vec![8..11, 12..15],
+        };
+        let options = |show_column| OutputOptions {
+            show_line_numbers: true,
+            show_column,
+            show_byte_offset: true,
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            format_match_with(&match_result, None, &options(Some(ColumnUnit::Chars))),
+            "2:8:40:façade foo foo".as_bytes()
+        );
+        assert_eq!(
+            format_match_with(&match_result, None, &options(Some(ColumnUnit::Bytes))),
+            "2:9:40:façade foo foo".as_bytes()
+        );
+        assert_eq!(
+            format_only_matching(&match_result, None, &options(Some(ColumnUnit::Chars))),
+            vec![b"2:8:48:foo".to_vec(), b"2:12:52:foo".to_vec()]
+        );
+
+        // Lines without a match have no column
+        let context = MatchResult {
+            spans: Vec::new(),
+            ..match_result
+        };
+        assert_eq!(
+            format_context(&context, None, &options(Some(ColumnUnit::Chars))),
+            "2-40-façade foo foo".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_format_count_and_filename() {
+        let options = OutputOptions {
+            show_filename: true,
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            format_count(3, Some(Path::new("a.txt")), &options),
+            b"a.txt:3"
+        );
+        assert_eq!(format_count(3, None, &options), b"3");
+        assert_eq!(
+            format_filename(Some(Path::new("a.txt")), &options),
+            b"a.txt"
+        );
+        assert_eq!(format_filename(None, &options), STDIN_LABEL.as_bytes());
+        assert_eq!(
+            format_binary_match(Some(Path::new("a.bin"))),
+            b"Binary file a.bin matches"
+        );
+    }
+
+    #[test]
+    fn test_format_null_after_filename() {
+        let match_result = MatchResult {
+            line: b"x:y".to_vec(),
+            line_number: 2,
+            byte_offset: 0,
+            matched: true,
+            spans: Vec::new(),
+        };
+        let options = OutputOptions {
+            show_filename: true,
+            show_line_numbers: true,
+            null: true,
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            format_match_with(&match_result, Some(Path::new("a:b")), &options),
+            b"a:b\x002:x:y"
+        );
+        assert_eq!(
+            format_context(&match_result, Some(Path::new("a:b")), &options),
+            b"a:b\x002-x:y"
+        );
+        assert_eq!(
+            format_count(3, Some(Path::new("a:b")), &options),
+            b"a:b\x003"
+        );
+    }
+
+    #[test]
+    fn test_regex_matcher() {
+        let matcher = RegexMatcher::new(r"^(foo|bar)\d+$", false).unwrap();
+        assert!(matcher.matches(b"foo42"));
+        assert!(matcher.matches(b"bar7"));
+        assert!(!matcher.matches(b"baz42"));
+        assert!(!matcher.matches(b"xfoo42"));
+
+        let matcher = RegexMatcher::new("[a-c]+z", true).unwrap();
+        assert!(matcher.matches(b"ABCZ"));
+        assert!(!matcher.matches(b"dz"));
+    }
+
+    #[test]
+    fn test_has_uppercase() {
+        assert!(!has_uppercase("todo", false));
+        assert!(has_uppercase("Todo", false));
+        assert!(has_uppercase("ÉCOLE", false));
+        assert!(has_uppercase(r"\S", false));
+
+        assert!(!has_uppercase(r"\S+\D\W\p{Lu}", true));
+        assert!(has_uppercase(r"\bFoo", true));
+        assert!(has_uppercase("[A-Z]x", true));
+        assert!(!has_uppercase("[^a-z]", true));
+        assert!(has_uppercase(r"\x41", true));
+    }
+
+    #[test]
+    fn test_regex_matcher_invalid_pattern() {
+        let err = RegexMatcher::new("(unclosed", false).err().unwrap();
+        assert_eq!(err.exit_code(), crate::errors::ExitCode::InvalidArgs);
     }
 
     #[test]
     fn test_format_match() {
         let match_result = MatchResult {
-            line: "hello world".to_string(),
+            line: b"hello world".to_vec(),
             line_number: 42,
+            byte_offset: 0,
             matched: true,
+            spans: Vec::new(),
         };
 
         // Basic formatting
         assert_eq!(
             format_match(&match_result, None, false, false),
-            "hello world"
+            b"hello world"
         );
 
         // With line numbers
         assert_eq!(
             format_match(&match_result, None, false, true),
-            "42:hello world"
+            b"42:hello world"
         );
 
         // With filename
         assert_eq!(
-            format_match(&match_result, Some("test.txt"), true, false),
-            "test.txt:hello world"
+            format_match(&match_result, Some(Path::new("test.txt")), true, false),
+            b"test.txt:hello world"
         );
 
         // With both filename and line numbers
         assert_eq!(
-            format_match(&match_result, Some("test.txt"), true, true),
-            "test.txt:42:hello world"
+            format_match(&match_result, Some(Path::new("test.txt")), true, true),
+            b"test.txt:42:hello world"
         );
     }
 }
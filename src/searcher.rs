@@ -0,0 +1,522 @@
+//! Chunked buffer search
+//!
+//! Input is read in large blocks and the matcher scans each block as a whole.
+//! Line boundaries are only located around candidate matches, so lines that
+//! are neither selected nor needed as context are never copied.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+use memchr::{memchr, memchr_iter, memrchr};
+
+use crate::search::{MatchResult, Matcher};
+
+/// Initial size of the read buffer; it grows to fit longer lines
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Iterator over the lines of a reader that the output needs
+///
+/// Yields every matching line, plus the `before` and `after` lines around
+/// them. With inverted matching every line may be selected, so every line is
+/// yielded. Line numbers count all lines, including those skipped.
+///
+/// With a maximum count, reading stops after that many selected lines and
+/// their trailing context; the trailing context ends early at the next line
+/// that would have been selected.
+pub struct BufferSearcher<'m, R> {
+    reader: R,
+    matcher: &'m dyn Matcher,
+    invert_match: bool,
+    max_count: Option<usize>,
+    /// Byte ending each line
+    terminator: u8,
+    before: usize,
+    after: usize,
+    buf: Vec<u8>,
+    /// Start of the first line not searched yet
+    pos: usize,
+    /// End of the data read into `buf`
+    end: usize,
+    /// Number of bytes dropped from the front of `buf` so far
+    discarded: usize,
+    /// Offset up to which line terminators have been counted
+    counted: usize,
+    /// Number of line terminators before `counted`
+    lines_before_counted: usize,
+    /// Number of the last yielded line (0 before the first)
+    last_yielded: usize,
+    /// Lines still to yield as context after the last match
+    after_remaining: usize,
+    /// Number of selected lines yielded
+    selected: usize,
+    /// Offset in the input just after the last selected line
+    selected_end: usize,
+    pending: VecDeque<MatchResult>,
+    eof: bool,
+}
+
+impl<'m, R: Read> BufferSearcher<'m, R> {
+    /// Search `reader` for lines matching `matcher`
+    pub fn new(reader: R, matcher: &'m dyn Matcher) -> Self {
+        Self {
+            reader,
+            matcher,
+            invert_match: false,
+            max_count: None,
+            terminator: b'\n',
+            before: 0,
+            after: 0,
+            buf: vec![0; DEFAULT_BUFFER_SIZE],
+            pos: 0,
+            end: 0,
+            discarded: 0,
+            counted: 0,
+            lines_before_counted: 0,
+            last_yielded: 0,
+            after_remaining: 0,
+            selected: 0,
+            selected_end: 0,
+            pending: VecDeque::new(),
+            eof: false,
+        }
+    }
+
+    /// Yield every line, as any non-matching line may be selected
+    pub fn invert_match(mut self, invert_match: bool) -> Self {
+        self.invert_match = invert_match;
+        self
+    }
+
+    /// Also yield up to `before` and `after` lines around each match
+    pub fn context(mut self, before: usize, after: usize) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    /// Stop after `max_count` selected lines, if given
+    pub fn max_count(mut self, max_count: Option<usize>) -> Self {
+        self.max_count = max_count;
+        self
+    }
+
+    /// Offset in the input just after the last selected line, if the search
+    /// stopped at the maximum count
+    ///
+    /// Input past this offset may have been read but was not searched.
+    pub fn resume_offset(&self) -> Option<usize> {
+        self.limit_reached().then_some(self.selected_end)
+    }
+
+    /// Whether the maximum number of selected lines has been yielded
+    fn limit_reached(&self) -> bool {
+        self.max_count.is_some_and(|max| self.selected >= max)
+    }
+
+    /// Record a selected line ending just before `next`
+    fn count_selected(&mut self, next: usize) {
+        self.selected += 1;
+        self.selected_end = self.discarded + next;
+    }
+
+    /// Split lines at `terminator` instead of `\n`
+    ///
+    /// A `\r` before the terminator is only stripped for `\n`.
+    pub fn line_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Set the initial size of the read buffer
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buf = vec![0; size.max(1)];
+        self
+    }
+
+    /// Make sure a whole line is buffered at `pos`
+    ///
+    /// Returns false once the input is exhausted. A read error ends the
+    /// input, like an unreadable line did for line-based reading.
+    fn fill(&mut self) -> bool {
+        loop {
+            if self.pos < self.end
+                && (self.eof || memchr(self.terminator, &self.buf[self.pos..self.end]).is_some())
+            {
+                return true;
+            }
+            if self.eof {
+                return false;
+            }
+
+            self.make_room();
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.end += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.eof = true,
+            }
+        }
+    }
+
+    /// Discard searched data, keeping the lines that may still be needed as
+    /// before-context, and grow the buffer if it is full
+    fn make_room(&mut self) {
+        self.count_lines_to(self.pos);
+
+        let mut keep_from = self.pos;
+        for _ in 0..self.before {
+            if keep_from == 0 {
+                break;
+            }
+            keep_from = memrchr(self.terminator, &self.buf[..keep_from - 1]).map_or(0, |i| i + 1);
+        }
+
+        if keep_from > 0 {
+            self.buf.copy_within(keep_from..self.end, 0);
+            self.pos -= keep_from;
+            self.end -= keep_from;
+            self.counted -= keep_from;
+            self.discarded += keep_from;
+        }
+
+        if self.end == self.buf.len() {
+            self.buf.resize(self.buf.len() * 2, 0);
+        }
+    }
+
+    /// Count line terminators up to `offset`
+    fn count_lines_to(&mut self, offset: usize) {
+        if offset > self.counted {
+            self.lines_before_counted +=
+                memchr_iter(self.terminator, &self.buf[self.counted..offset]).count();
+            self.counted = offset;
+        }
+    }
+
+    /// End of the complete lines buffered from `pos`
+    fn region_end(&self) -> usize {
+        if self.eof {
+            self.end
+        } else {
+            memrchr(self.terminator, &self.buf[self.pos..self.end])
+                .map_or(self.pos, |i| self.pos + i + 1)
+        }
+    }
+
+    /// End of the line content and start of the next line for a line
+    /// containing `offset`
+    fn line_end(&self, offset: usize, region_end: usize) -> (usize, usize) {
+        match memchr(self.terminator, &self.buf[offset..region_end]) {
+            Some(i) => (offset + i, offset + i + 1),
+            None => (region_end, region_end),
+        }
+    }
+
+    /// Line content between `start` and `end`, without a trailing `\r`
+    fn line(&self, start: usize, end: usize) -> &[u8] {
+        let line = &self.buf[start..end];
+        match self.terminator {
+            b'\n' => line.strip_suffix(b"\r").unwrap_or(line),
+            _ => line,
+        }
+    }
+
+    /// Build the result for a line whose match status is known
+    fn result(&self, start: usize, end: usize, line_number: usize, matched: bool) -> MatchResult {
+        let line = self.line(start, end);
+        let spans = if matched {
+            self.matcher.find_all(line)
+        } else {
+            Vec::new()
+        };
+
+        MatchResult {
+            line: line.to_vec(),
+            line_number,
+            byte_offset: self.discarded + start,
+            matched,
+            spans,
+        }
+    }
+
+    /// Find the first matching line in `pos..region_end`
+    ///
+    /// Returns the start, content end and next line start of that line.
+    fn find_match(&self, region_end: usize) -> Option<(usize, usize, usize)> {
+        let haystack = &self.buf[..region_end];
+        let mut from = self.pos;
+
+        while from < region_end {
+            let hit = self.matcher.find_candidate(haystack, from)?;
+            if hit >= region_end {
+                return None;
+            }
+
+            let start =
+                memrchr(self.terminator, &self.buf[from..hit]).map_or(from, |i| from + i + 1);
+            let (end, next) = self.line_end(hit, region_end);
+
+            if self.matcher.matches(self.line(start, end)) {
+                return Some((start, end, next));
+            }
+            from = next;
+        }
+
+        None
+    }
+
+    /// Queue the unyielded lines just before the line starting at `start`
+    fn queue_before_context(&mut self, start: usize, line_number: usize) {
+        let wanted = self.before.min(line_number - 1 - self.last_yielded);
+        let mut lines = Vec::with_capacity(wanted);
+        let mut line_start = start;
+
+        for n in 1..=wanted {
+            if line_start == 0 {
+                break;
+            }
+            let end = line_start - 1;
+            line_start = memrchr(self.terminator, &self.buf[..end]).map_or(0, |i| i + 1);
+            // Lines between matches are known not to match
+            lines.push(self.result(line_start, end, line_number - n, false));
+        }
+
+        self.pending.extend(lines.into_iter().rev());
+    }
+}
+
+impl<R: Read> Iterator for BufferSearcher<'_, R> {
+    type Item = MatchResult;
+
+    fn next(&mut self) -> Option<MatchResult> {
+        loop {
+            if let Some(result) = self.pending.pop_front() {
+                return Some(result);
+            }
+            if self.limit_reached() && self.after_remaining == 0 {
+                return None;
+            }
+            if !self.fill() {
+                return None;
+            }
+
+            let region_end = self.region_end();
+
+            if self.invert_match || self.after_remaining > 0 {
+                // Yield the next line on its own
+                let start = self.pos;
+                let (end, next) = self.line_end(start, region_end);
+                self.count_lines_to(start);
+                let line_number = self.lines_before_counted + 1;
+                let matched = self.matcher.matches(self.line(start, end));
+
+                if matched != self.invert_match {
+                    // Trailing context after the last allowed line ends here
+                    if self.limit_reached() {
+                        self.after_remaining = 0;
+                        return None;
+                    }
+                    self.count_selected(next);
+                    self.after_remaining = self.after;
+                } else {
+                    self.after_remaining = self.after_remaining.saturating_sub(1);
+                }
+
+                let result = self.result(start, end, line_number, matched);
+                self.pos = next;
+                self.last_yielded = line_number;
+                return Some(result);
+            }
+
+            match self.find_match(region_end) {
+                Some((start, end, next)) => {
+                    self.count_lines_to(start);
+                    let line_number = self.lines_before_counted + 1;
+
+                    self.queue_before_context(start, line_number);
+                    let result = self.result(start, end, line_number, true);
+                    self.pending.push_back(result);
+                    self.count_selected(next);
+
+                    self.after_remaining = self.after;
+                    self.pos = next;
+                    self.last_yielded = line_number;
+                }
+                None => self.pos = region_end,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{search_lines, LiteralMatcher, RegexMatcher};
+
+    /// Lines yielded by the buffer searcher, as (line number, line, matched)
+    fn buffered(
+        input: &[u8],
+        matcher: &dyn Matcher,
+        invert: bool,
+        context: (usize, usize),
+        buffer_size: usize,
+    ) -> Vec<(usize, Vec<u8>, bool)> {
+        BufferSearcher::new(input, matcher)
+            .invert_match(invert)
+            .context(context.0, context.1)
+            .buffer_size(buffer_size)
+            .map(|m| (m.line_number, m.line, m.matched))
+            .collect()
+    }
+
+    /// Lines the buffer searcher must yield, computed line by line
+    fn expected(
+        input: &[u8],
+        matcher: &dyn Matcher,
+        invert: bool,
+        (before, after): (usize, usize),
+    ) -> Vec<(usize, Vec<u8>, bool)> {
+        let text = String::from_utf8_lossy(input).to_string();
+        let lines = text
+            .lines()
+            .map(|line| Ok(line.as_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        let all: Vec<_> = search_lines(lines.into_iter(), matcher).collect();
+
+        let wanted = |idx: usize| {
+            invert
+                || all[idx.saturating_sub(after)..=(idx + before).min(all.len() - 1)]
+                    .iter()
+                    .any(|m| m.matched)
+        };
+
+        all.iter()
+            .enumerate()
+            .filter(|&(idx, _)| wanted(idx))
+            .map(|(_, m)| (m.line_number, m.line.clone(), m.matched))
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_across_buffer_boundaries() {
+        let input = b"alpha\nfoo one\nbeta\r\n\ngamma foo\nfoofoo\ndelta\nlast foo";
+        let literal = LiteralMatcher::new("foo".to_string());
+        let anchored = RegexMatcher::new("^foo|a$", false).unwrap();
+        let empty = RegexMatcher::new("x*", false).unwrap();
+        let matchers: [&dyn Matcher; 3] = [&literal, &anchored, &empty];
+
+        for matcher in matchers {
+            for invert in [false, true] {
+                for context in [(0, 0), (1, 0), (0, 2), (2, 1)] {
+                    let want = expected(input, matcher, invert, context);
+                    for size in [1, 3, 7, 16, 4096] {
+                        assert_eq!(
+                            buffered(input, matcher, invert, context, size),
+                            want,
+                            "invert {invert}, context {context:?}, buffer {size}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_numbers_and_crlf() {
+        let matcher = LiteralMatcher::new("x".to_string());
+        let input = b"a\r\nx\r\nb\r\nc\r\nx";
+        assert_eq!(
+            buffered(input, &matcher, false, (0, 0), 4),
+            vec![(2, b"x".to_vec(), true), (5, b"x".to_vec(), true)]
+        );
+    }
+
+    #[test]
+    fn test_byte_offsets() {
+        let matcher = LiteralMatcher::new("x".to_string());
+        let input = b"a\r\nx\nbb\nbx\nccc\nx";
+        for size in [1, 4, 4096] {
+            let offsets: Vec<_> = BufferSearcher::new(&input[..], &matcher)
+                .context(1, 0)
+                .buffer_size(size)
+                .map(|m| (m.line_number, m.byte_offset))
+                .collect();
+            assert_eq!(
+                offsets,
+                vec![(1, 0), (2, 3), (3, 5), (4, 8), (5, 11), (6, 15)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_count() {
+        let matcher = LiteralMatcher::new("x".to_string());
+        let input = b"x1\na\nx2\nb\nc\nx3\nd\n";
+        let search = |invert, after, max, size| {
+            let mut searcher = BufferSearcher::new(&input[..], &matcher)
+                .invert_match(invert)
+                .context(0, after)
+                .max_count(Some(max))
+                .buffer_size(size);
+            let lines: Vec<_> = searcher.by_ref().map(|m| m.line_number).collect();
+            (lines, searcher.resume_offset())
+        };
+
+        for size in [1, 5, 4096] {
+            assert_eq!(search(false, 0, 2, size), (vec![1, 3], Some(8)));
+            // Trailing context is kept, but stops at the next selected line
+            assert_eq!(search(false, 1, 1, size), (vec![1, 2], Some(3)));
+            assert_eq!(search(false, 5, 2, size), (vec![1, 2, 3, 4, 5], Some(8)));
+            assert_eq!(search(true, 0, 2, size), (vec![1, 2, 3, 4], Some(10)));
+            assert_eq!(search(false, 0, 0, size), (vec![], Some(0)));
+        }
+
+        // Running out of input before the limit leaves nothing to resume
+        let mut searcher = BufferSearcher::new(&input[..], &matcher).max_count(Some(9));
+        assert_eq!(searcher.by_ref().count(), 3);
+        assert_eq!(searcher.resume_offset(), None);
+    }
+
+    #[test]
+    fn test_nul_terminated_lines() {
+        let literal = LiteralMatcher::new("foo".to_string());
+        // `^` must match after NUL, but not after a newline within a line
+        let anchored = RegexMatcher::with_line_terminator(&["^foo"], false, b'\0').unwrap();
+        let input = b"a\nfoo\0foo\r\nb\0c\0foo";
+        let cases: [(&dyn Matcher, &[usize]); 2] = [(&literal, &[1, 2, 4]), (&anchored, &[2, 4])];
+
+        for (matcher, line_numbers) in cases {
+            for size in [1, 4, 4096] {
+                let lines: Vec<_> = BufferSearcher::new(&input[..], matcher)
+                    .line_terminator(b'\0')
+                    .buffer_size(size)
+                    .map(|m| (m.line_number, m.line, m.byte_offset))
+                    .collect();
+                let all = [
+                    (1, b"a\nfoo".to_vec(), 0),
+                    (2, b"foo\r\nb".to_vec(), 6),
+                    (4, b"foo".to_vec(), 15),
+                ];
+                let want: Vec<_> = all
+                    .into_iter()
+                    .filter(|(n, _, _)| line_numbers.contains(n))
+                    .collect();
+                assert_eq!(lines, want, "buffer {size}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_trailing_empty_line() {
+        let matcher = RegexMatcher::new("^", false).unwrap();
+        let lines = buffered(b"a\n\nb\n", &matcher, false, (0, 0), 2);
+        assert_eq!(
+            lines,
+            vec![
+                (1, b"a".to_vec(), true),
+                (2, b"".to_vec(), true),
+                (3, b"b".to_vec(), true)
+            ]
+        );
+    }
+}
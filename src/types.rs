@@ -0,0 +1,135 @@
+//! Named file types (`-t`, `-T`, `--type-list`, `--type-add`)
+
+use std::collections::BTreeMap;
+
+use crate::errors::RgrepError;
+
+/// Built-in file types and the globs they match
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.htm", "*.html"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml", "*.xsd", "*.xsl"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// Registry mapping file type names to globs
+#[derive(Debug, Clone)]
+pub struct TypeRegistry {
+    types: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for TypeRegistry {
+    fn default() -> Self {
+        let types = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| {
+                let globs = globs.iter().map(|g| g.to_string()).collect();
+                (name.to_string(), globs)
+            })
+            .collect();
+
+        Self { types }
+    }
+}
+
+impl TypeRegistry {
+    /// Create a registry with the built-in types plus `name:glob` additions
+    pub fn with_additions(additions: &[String]) -> Result<Self, RgrepError> {
+        let mut registry = Self::default();
+        for spec in additions {
+            registry.add(spec)?;
+        }
+        Ok(registry)
+    }
+
+    /// Add a glob to a type from a `name:glob` definition
+    ///
+    /// Unknown names create a new type; known names gain another glob.
+    pub fn add(&mut self, spec: &str) -> Result<(), RgrepError> {
+        let (name, glob) = spec
+            .split_once(':')
+            .filter(|(name, glob)| !name.is_empty() && !glob.is_empty())
+            .ok_or_else(|| {
+                RgrepError::invalid_args(
+                    format!("Invalid type definition '{spec}': expected 'name:glob'"),
+                    false,
+                )
+            })?;
+
+        self.types
+            .entry(name.to_string())
+            .or_default()
+            .push(glob.to_string());
+
+        Ok(())
+    }
+
+    /// Get the globs of a type
+    pub fn globs(&self, name: &str) -> Result<&[String], RgrepError> {
+        self.types.get(name).map(Vec::as_slice).ok_or_else(|| {
+            RgrepError::invalid_args(
+                format!("Unknown file type '{name}' (see --type-list)"),
+                false,
+            )
+        })
+    }
+
+    /// Render the table printed by `--type-list`, one type per line
+    pub fn list(&self) -> String {
+        self.types
+            .iter()
+            .map(|(name, globs)| format!("{name}: {}", globs.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_types() {
+        let registry = TypeRegistry::default();
+        assert_eq!(registry.globs("rust").unwrap(), ["*.rs"]);
+        assert_eq!(registry.globs("py").unwrap(), ["*.py", "*.pyi"]);
+        assert!(registry.globs("nope").is_err());
+    }
+
+    #[test]
+    fn test_type_add() {
+        let additions = vec!["rust:*.rs.in".to_string(), "proto:*.proto".to_string()];
+        let registry = TypeRegistry::with_additions(&additions).unwrap();
+        assert_eq!(registry.globs("rust").unwrap(), ["*.rs", "*.rs.in"]);
+        assert_eq!(registry.globs("proto").unwrap(), ["*.proto"]);
+
+        let mut registry = TypeRegistry::default();
+        assert!(registry.add("missing-colon").is_err());
+        assert!(registry.add(":*.x").is_err());
+        assert!(registry.add("x:").is_err());
+    }
+
+    #[test]
+    fn test_list_is_sorted() {
+        let list = TypeRegistry::default().list();
+        let first = list.lines().next().unwrap();
+        assert_eq!(first, "c: *.c, *.h");
+        assert!(list.contains("\nrust: *.rs\n"));
+    }
+}
@@ -0,0 +1,284 @@
+//! Recursive directory traversal for `-r` / `-R`
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::errors::RgrepError;
+use crate::filter::PathFilter;
+use crate::ignore::Gitignore;
+
+/// A path waiting to be visited by the walker
+struct Pending {
+    /// Path as it will be displayed
+    path: PathBuf,
+    /// Canonical paths of the directories leading to this path
+    ancestors: Vec<PathBuf>,
+    /// Ignore rules in effect for this path, shallowest first
+    ignores: Vec<Rc<IgnoreLayer>>,
+    /// Whether this path was given by the user rather than discovered
+    is_root: bool,
+}
+
+/// Ignore rules together with the part of the tree they apply to
+struct IgnoreLayer {
+    /// Walked directory that paths are made relative to
+    dir: PathBuf,
+    /// Location of `dir` relative to the directory holding the ignore file
+    prefix: PathBuf,
+    /// The parsed rules
+    rules: Gitignore,
+}
+
+impl IgnoreLayer {
+    /// Decide whether `path`, which lies below `dir`, is ignored
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        self.rules
+            .matched(&slash_path(&self.prefix.join(relative)), is_dir)
+    }
+}
+
+/// Depth-first iterator over the regular files below a set of roots
+///
+/// Directory entries are visited in name order so output is deterministic.
+/// Symbolic links found during traversal are only followed when
+/// `follow_links` is set; roots are always followed, like GNU grep.
+///
+/// By default hidden entries are skipped and ignore files are honoured:
+/// `.gitignore`, `.ignore` and `.rgrepignore` in every visited directory,
+/// plus those of parent directories up to the enclosing git repository and
+/// its `.git/info/exclude`. Roots themselves are never filtered, neither by
+/// ignore files nor by glob filters.
+pub struct Walk {
+    root: PathBuf,
+    stack: Vec<Pending>,
+    follow_links: bool,
+    respect_ignore: bool,
+    hidden: bool,
+    filter: Option<PathFilter>,
+}
+
+impl Walk {
+    /// Create a walker starting at `root`
+    pub fn new<P: AsRef<Path>>(root: P, follow_links: bool) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            stack: vec![Pending {
+                path: root.as_ref().to_path_buf(),
+                ancestors: Vec::new(),
+                ignores: Vec::new(),
+                is_root: true,
+            }],
+            follow_links,
+            respect_ignore: true,
+            hidden: false,
+            filter: None,
+        }
+    }
+
+    /// Set whether ignore files are honoured (default: true)
+    pub fn respect_ignore(mut self, respect_ignore: bool) -> Self {
+        self.respect_ignore = respect_ignore;
+        self
+    }
+
+    /// Set whether hidden files and directories are visited (default: false)
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Restrict discovered paths with include/exclude globs
+    ///
+    /// Globs are matched against paths relative to the root.
+    pub fn filter(mut self, filter: PathFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Whether a discovered path should be skipped
+    fn is_filtered(&self, pending: &Pending, is_dir: bool) -> bool {
+        let name = pending.path.file_name().unwrap_or_default();
+
+        if !self.hidden && name.to_string_lossy().starts_with('.') {
+            return true;
+        }
+
+        if let Some(filter) = &self.filter {
+            let relative = pending
+                .path
+                .strip_prefix(&self.root)
+                .unwrap_or(&pending.path);
+            let relative = slash_path(relative);
+            let allowed = if is_dir {
+                filter.allows_dir(&relative)
+            } else {
+                filter.allows_file(&relative)
+            };
+
+            if !allowed {
+                return true;
+            }
+        }
+
+        if !self.respect_ignore {
+            return false;
+        }
+
+        if is_dir && name == ".git" {
+            return true;
+        }
+
+        pending
+            .ignores
+            .iter()
+            .rev()
+            .find_map(|layer| layer.matched(&pending.path, is_dir))
+            .unwrap_or(false)
+    }
+
+    /// Queue the entries of a directory for visiting
+    fn push_children(&mut self, dir: &Pending, canonical: PathBuf) -> Result<(), RgrepError> {
+        let to_error = |e| RgrepError::io_error(dir.path.to_string_lossy(), e);
+
+        let mut children = fs::read_dir(&dir.path)
+            .map_err(to_error)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(to_error)?;
+        children.sort();
+
+        let mut ignores = dir.ignores.clone();
+        if self.respect_ignore {
+            if dir.is_root {
+                ignores.extend(parent_layers(&dir.path, &canonical));
+            }
+            if let Some(rules) = Gitignore::from_dir(&dir.path) {
+                ignores.push(Rc::new(IgnoreLayer {
+                    dir: dir.path.clone(),
+                    prefix: PathBuf::new(),
+                    rules,
+                }));
+            }
+        }
+
+        let mut ancestors = dir.ancestors.clone();
+        ancestors.push(canonical);
+
+        // Reverse so the smallest name is popped first
+        self.stack
+            .extend(children.into_iter().rev().map(|path| Pending {
+                path,
+                ancestors: ancestors.clone(),
+                ignores: ignores.clone(),
+                is_root: false,
+            }));
+
+        Ok(())
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<PathBuf, RgrepError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(pending) = self.stack.pop() {
+            let path_str = pending.path.to_string_lossy().to_string();
+
+            let metadata = if pending.is_root || self.follow_links {
+                fs::metadata(&pending.path)
+            } else {
+                fs::symlink_metadata(&pending.path)
+            };
+
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(e) => return Some(Err(RgrepError::io_error(path_str, e))),
+            };
+
+            if !pending.is_root && self.is_filtered(&pending, metadata.is_dir()) {
+                continue;
+            }
+
+            if metadata.is_file() {
+                return Some(Ok(pending.path));
+            }
+
+            if !metadata.is_dir() {
+                // Unfollowed symlinks, sockets, FIFOs and devices are skipped
+                continue;
+            }
+
+            let canonical = match fs::canonicalize(&pending.path) {
+                Ok(canonical) => canonical,
+                Err(e) => return Some(Err(RgrepError::io_error(path_str, e))),
+            };
+
+            if pending.ancestors.contains(&canonical) {
+                return Some(Err(RgrepError::app_error(format!(
+                    "warning: {path_str}: recursive directory loop"
+                ))));
+            }
+
+            if let Err(error) = self.push_children(&pending, canonical) {
+                return Some(Err(error));
+            }
+        }
+
+        None
+    }
+}
+
+/// Ignore rules of the directories above a root, inside its git repository
+fn parent_layers(root: &Path, canonical_root: &Path) -> Vec<Rc<IgnoreLayer>> {
+    let Some(repo) = canonical_root
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+    else {
+        return Vec::new();
+    };
+
+    let layer = |dir: &Path, rules| {
+        Rc::new(IgnoreLayer {
+            dir: root.to_path_buf(),
+            prefix: canonical_root
+                .strip_prefix(dir)
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+            rules,
+        })
+    };
+
+    let mut layers = Vec::new();
+
+    if let Some(rules) = Gitignore::from_file(&repo.join(".git/info/exclude")) {
+        layers.push(layer(repo, rules));
+    }
+
+    let parents: Vec<&Path> = canonical_root
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(repo))
+        .collect();
+
+    for dir in parents.into_iter().rev() {
+        if let Some(rules) = Gitignore::from_dir(dir) {
+            layers.push(layer(dir, rules));
+        }
+    }
+
+    layers
+}
+
+/// Render a relative path with `/` separators, dropping `.` components
+pub fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by the integration tests
+
+use rgrep::cli::{parse_args, CliAction, Config};
+
+/// Parse arguments that must start a search, returning its configuration
+pub fn run_config(args: &[&str]) -> Config {
+    match parse_args(args.iter().map(|s| s.to_string())) {
+        Ok(CliAction::Run(cfg)) => *cfg,
+        Ok(_) => panic!("expected Run"),
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+}
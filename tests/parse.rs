@@ -1,4 +1,7 @@
-use rgrep::cli::{parse_args, CliAction};
+mod common;
+
+use common::run_config;
+use rgrep::cli::{parse_args, BinaryFiles, CliAction, ColumnUnit, OutputMode, SortBy};
 
 #[test]
 fn parse_minimal_pattern() {
@@ -75,3 +78,339 @@ fn parse_invalid_mixing_stdin_and_files() {
     let r = parse_args(["pattern", "-", "file.txt"].into_iter().map(String::from));
     assert!(r.is_err());
 }
+
+#[test]
+fn parse_regex_flag() {
+    let cfg = run_config(&["-E", "fo+", "file.txt"]);
+    assert_eq!(cfg.pattern, "fo+");
+    assert!(cfg.regex);
+}
+
+#[test]
+fn parse_color_choice() {
+    use rgrep::color::ColorChoice;
+
+    let parse_color = |args: &[&str]| run_config(args).color;
+
+    assert_eq!(parse_color(&["foo"]), ColorChoice::Auto);
+    assert_eq!(parse_color(&["--color=always", "foo"]), ColorChoice::Always);
+    assert_eq!(parse_color(&["--color=never", "foo"]), ColorChoice::Never);
+    assert_eq!(parse_color(&["--color", "foo"]), ColorChoice::Auto);
+    assert!(parse_args(["--color=sometimes", "foo"].into_iter().map(String::from)).is_err());
+}
+
+#[test]
+fn parse_invert_match() {
+    assert!(run_config(&["-v", "foo"]).invert_match);
+}
+
+#[test]
+fn parse_output_modes() {
+    let parse_mode = |flag: &str| run_config(&[flag, "foo"]).output_mode;
+
+    assert_eq!(parse_mode("-n"), OutputMode::Lines);
+    assert_eq!(parse_mode("-c"), OutputMode::Count);
+    assert_eq!(parse_mode("-l"), OutputMode::FilesWithMatches);
+    assert_eq!(parse_mode("-L"), OutputMode::FilesWithoutMatch);
+    assert_eq!(parse_mode("--json"), OutputMode::Json);
+    assert_eq!(parse_mode("-q"), OutputMode::Quiet);
+}
+
+#[test]
+fn parse_conflicting_output_modes() {
+    let r = parse_args(["-c", "-l", "foo"].into_iter().map(String::from));
+    assert!(r.is_err());
+    let r = parse_args(["--json", "-c", "foo"].into_iter().map(String::from));
+    assert!(r.is_err());
+}
+
+#[test]
+fn parse_context_flags() {
+    let parse_context = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.before_context, cfg.after_context)
+    };
+
+    assert_eq!(parse_context(&["foo"]), (0, 0));
+    assert_eq!(parse_context(&["-A", "2", "foo"]), (0, 2));
+    assert_eq!(parse_context(&["-B", "3", "foo"]), (3, 0));
+    assert_eq!(parse_context(&["-C", "1", "foo"]), (1, 1));
+    assert_eq!(parse_context(&["-C", "1", "-A", "4", "foo"]), (1, 4));
+    assert!(parse_args(["-A", "x", "foo"].into_iter().map(String::from)).is_err());
+}
+
+#[test]
+fn parse_glob_filters() {
+    let cfg = run_config(&[
+        "-r",
+        "--include",
+        "*.rs",
+        "--exclude=*.min.js",
+        "--exclude-dir",
+        "vendor",
+        "-g",
+        "!*.lock",
+        "--glob=*.toml",
+        "foo",
+    ]);
+    assert_eq!(cfg.include, vec!["*.rs"]);
+    assert_eq!(cfg.exclude, vec!["*.min.js"]);
+    assert_eq!(cfg.exclude_dir, vec!["vendor"]);
+    assert_eq!(cfg.globs, vec!["!*.lock", "*.toml"]);
+}
+
+#[test]
+fn parse_file_types() {
+    let cfg = run_config(&[
+        "-r",
+        "-t",
+        "rust",
+        "-T",
+        "js",
+        "--type-add",
+        "proto:*.proto",
+        "foo",
+    ]);
+    assert_eq!(cfg.types, vec!["rust"]);
+    assert_eq!(cfg.types_not, vec!["js"]);
+    assert_eq!(cfg.type_add, vec!["proto:*.proto"]);
+}
+
+#[test]
+fn parse_type_list_without_pattern() {
+    let r = parse_args(
+        ["--type-add", "proto:*.proto", "--type-list"]
+            .into_iter()
+            .map(String::from),
+    )
+    .unwrap();
+    match r {
+        CliAction::ShowTypeList(registry) => {
+            assert!(registry.list().contains("proto: *.proto"));
+        }
+        _ => panic!("expected ShowTypeList"),
+    }
+
+    let r = parse_args(
+        ["--type-add", "bad", "--type-list"]
+            .into_iter()
+            .map(String::from),
+    );
+    assert!(r.is_err());
+}
+
+#[test]
+fn parse_binary_files() {
+    let parse_binary = |args: &[&str]| run_config(args).binary_files;
+
+    assert_eq!(parse_binary(&["foo"]), BinaryFiles::Binary);
+    assert_eq!(parse_binary(&["-a", "foo"]), BinaryFiles::Text);
+    assert_eq!(parse_binary(&["-I", "foo"]), BinaryFiles::WithoutMatch);
+    assert_eq!(
+        parse_binary(&["--binary-files=without-match", "foo"]),
+        BinaryFiles::WithoutMatch
+    );
+    assert_eq!(
+        parse_binary(&["--binary-files", "text", "foo"]),
+        BinaryFiles::Text
+    );
+}
+
+#[test]
+fn parse_threads_and_sort() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.threads, cfg.sort)
+    };
+
+    assert_eq!(parse(&["foo"]), (None, SortBy::None));
+    assert_eq!(parse(&["-j", "4", "foo"]), (Some(4), SortBy::None));
+    assert_eq!(
+        parse(&["--threads=2", "--sort", "path", "foo"]),
+        (Some(2), SortBy::Path)
+    );
+
+    assert!(parse_args(["-j", "0", "foo"].into_iter().map(String::from)).is_err());
+    assert!(parse_args(["--sort", "size", "foo"].into_iter().map(String::from)).is_err());
+}
+
+#[test]
+fn parse_case_flags_last_wins() {
+    let parse_case = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.ignore_case, cfg.smart_case)
+    };
+
+    assert_eq!(parse_case(&["foo"]), (false, false));
+    assert_eq!(parse_case(&["-S", "foo"]), (false, true));
+    assert_eq!(parse_case(&["--smart-case", "-i", "foo"]), (true, false));
+    assert_eq!(parse_case(&["-i", "-S", "foo"]), (false, true));
+    assert_eq!(parse_case(&["-i", "-s", "foo"]), (false, false));
+    assert_eq!(
+        parse_case(&["-S", "--case-sensitive", "foo"]),
+        (false, false)
+    );
+    assert_eq!(parse_case(&["-s", "-i", "foo"]), (true, false));
+}
+
+#[test]
+fn parse_word_and_line_regexp() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.word_regexp, cfg.line_regexp)
+    };
+
+    assert_eq!(parse(&["foo"]), (false, false));
+    assert_eq!(parse(&["-w", "foo"]), (true, false));
+    assert_eq!(parse(&["--line-regexp", "foo"]), (false, true));
+    assert_eq!(parse(&["-wx", "foo"]), (true, true));
+}
+
+#[test]
+fn parse_column_and_byte_offset() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.pattern, cfg.column, cfg.byte_offset)
+    };
+
+    assert_eq!(parse(&["foo"]), ("foo".into(), None, false));
+    assert_eq!(
+        parse(&["--column", "foo"]),
+        ("foo".into(), Some(ColumnUnit::Chars), false)
+    );
+    assert_eq!(
+        parse(&["--column=bytes", "-b", "foo"]),
+        ("foo".into(), Some(ColumnUnit::Bytes), true)
+    );
+}
+
+#[test]
+fn parse_max_count_and_quiet() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.max_count, cfg.output_mode)
+    };
+
+    assert_eq!(parse(&["foo"]), (None, OutputMode::Lines));
+    assert_eq!(parse(&["-m", "3", "foo"]), (Some(3), OutputMode::Lines));
+    // Quiet wins over the other output modes
+    assert_eq!(parse(&["-c", "--silent", "foo"]), (None, OutputMode::Quiet));
+    assert_eq!(
+        parse(&["-q", "-l", "-m0", "foo"]),
+        (Some(0), OutputMode::Quiet)
+    );
+    assert!(parse_args(["-m", "-1", "foo"].into_iter().map(String::from)).is_err());
+}
+
+#[test]
+fn parse_null_flags() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.null, cfg.null_data, cfg.line_terminator())
+    };
+
+    assert_eq!(parse(&["foo"]), (false, false, b'\n'));
+    assert_eq!(parse(&["-Z", "foo"]), (true, false, b'\n'));
+    assert_eq!(parse(&["--null-data", "foo"]), (false, true, b'\0'));
+    assert_eq!(parse(&["-lzZ", "foo"]), (true, true, b'\0'));
+}
+
+#[test]
+fn parse_replace() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.replace, cfg.recursive)
+    };
+
+    assert_eq!(parse(&["foo"]), (None, false));
+    assert_eq!(
+        parse(&["--replace", "-$1-", "foo"]),
+        (Some("-$1-".to_string()), false)
+    );
+    // -r stays recursive; replacing has only the long form
+    assert_eq!(
+        parse(&["-r", "--replace=", "foo"]),
+        (Some(String::new()), true)
+    );
+}
+
+#[test]
+fn parse_in_place() {
+    let parse = |args: &[&str]| {
+        let cfg = run_config(args);
+        (cfg.in_place, cfg.backup, cfg.dry_run)
+    };
+
+    assert_eq!(parse(&["foo", "f.txt"]), (false, false, false));
+    assert_eq!(
+        parse(&["--replace", "x", "--in-place", "foo", "f.txt"]),
+        (true, false, false)
+    );
+    assert_eq!(
+        parse(&[
+            "--replace=x",
+            "--in-place",
+            "--backup",
+            "--dry-run",
+            "foo",
+            "f"
+        ]),
+        (true, true, true)
+    );
+
+    // Each flag needs the one before it, and a file to rewrite
+    for args in [
+        &["--in-place", "foo", "f.txt"][..],
+        &["--replace", "x", "--backup", "foo", "f.txt"],
+        &["--replace", "x", "--dry-run", "foo", "f.txt"],
+        &["--replace", "x", "--in-place", "foo"],
+        &["--replace", "x", "--in-place", "-c", "foo", "f.txt"],
+    ] {
+        assert!(parse_args(args.iter().map(|s| s.to_string())).is_err());
+    }
+}
+
+#[test]
+fn parse_multiple_patterns() {
+    let cfg = run_config(&["foo", "a.txt"]);
+    assert_eq!(cfg.all_patterns().collect::<Vec<_>>(), ["foo"]);
+    assert_eq!(cfg.files, ["a.txt"]);
+
+    // With -e, the first positional argument is a file
+    let cfg = run_config(&["-e", "foo", "-e", "-bar", "a.txt", "b.txt"]);
+    assert_eq!(cfg.all_patterns().collect::<Vec<_>>(), ["foo", "-bar"]);
+    assert_eq!(cfg.files, ["a.txt", "b.txt"]);
+
+    let mut pattern_file = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut pattern_file, b"one\r\ntwo\n").unwrap();
+    let path = pattern_file.path().to_str().unwrap();
+
+    let cfg = run_config(&["-e", "zero", "-f", path, "--pattern=three", "a.txt"]);
+    assert_eq!(
+        cfg.all_patterns().collect::<Vec<_>>(),
+        ["zero", "one", "two", "three"]
+    );
+    assert_eq!(cfg.files, ["a.txt"]);
+
+    // An empty pattern file gives no patterns, rather than an error
+    let empty = tempfile::NamedTempFile::new().unwrap();
+    let cfg = run_config(&["-f", empty.path().to_str().unwrap(), "a.txt"]);
+    assert!(cfg.no_patterns);
+    assert_eq!(cfg.all_patterns().count(), 0);
+}
+
+#[test]
+fn parse_pattern_errors() {
+    let parse = |args: &[&str]| parse_args(args.iter().map(|s| s.to_string()));
+
+    assert!(parse(&["-f", "/nonexistent/patterns"]).is_err());
+    assert!(parse(&["-e", ""]).is_err());
+
+    let mut blank_line = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut blank_line, b"one\n\ntwo\n").unwrap();
+    let error = parse(&["-f", blank_line.path().to_str().unwrap()]).unwrap_err();
+    assert!(error.to_string().contains("line 2"));
+    // Patterns and input cannot both come from stdin
+    assert!(parse(&["-f", "-"]).is_err());
+    assert!(parse(&["-f", "-", "-"]).is_err());
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::Path;
+
+use rgrep::app::execute_search;
+use rgrep::{Config, ExitCode, RgrepError};
+use tempfile::TempDir;
+
+fn files(dir: &TempDir, contents: &[(&str, &str)]) -> Vec<String> {
+    contents
+        .iter()
+        .map(|(name, text)| {
+            let path = dir.path().join(name);
+            fs::write(&path, text).unwrap();
+            path.to_string_lossy().to_string()
+        })
+        .collect()
+}
+
+fn in_place(pattern: &str, replacement: &str, files: Vec<String>) -> Config {
+    let mut config = Config::new(pattern.to_string(), files, false, false);
+    config.replace = Some(replacement.to_string());
+    config.in_place = true;
+    config
+}
+
+fn read(path: impl AsRef<Path>) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn rewrite_files_in_place() {
+    let dir = TempDir::new().unwrap();
+    let paths = files(
+        &dir,
+        &[("a.txt", "let id = 1;\nid\n"), ("b.txt", "nothing\n")],
+    );
+
+    let mut config = in_place(r"(\w+) = (\d+)", "$2 = $1", paths.clone());
+    config.regex = true;
+    assert_eq!(execute_search(&config).unwrap(), ExitCode::Success);
+    assert_eq!(read(&paths[0]), "let 1 = id;\nid\n");
+    assert_eq!(read(&paths[1]), "nothing\n");
+    assert!(!dir.path().join("a.txt.bak").exists());
+
+    // Nothing left to replace
+    assert_eq!(execute_search(&config).unwrap(), ExitCode::NoMatches);
+}
+
+#[cfg(unix)]
+#[test]
+fn rewrite_keeps_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let paths = files(&dir, &[("run.sh", "echo foo\n")]);
+    fs::set_permissions(&paths[0], fs::Permissions::from_mode(0o750)).unwrap();
+
+    execute_search(&in_place("foo", "bar", paths.clone())).unwrap();
+    assert_eq!(read(&paths[0]), "echo bar\n");
+    let mode = fs::metadata(&paths[0]).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+}
+
+#[test]
+fn rewrite_with_backup() {
+    let dir = TempDir::new().unwrap();
+    let paths = files(&dir, &[("a.txt", "foo\n"), ("b.txt", "bar\n")]);
+
+    let mut config = in_place("foo", "baz", paths.clone());
+    config.backup = true;
+    assert_eq!(execute_search(&config).unwrap(), ExitCode::Success);
+    assert_eq!(read(&paths[0]), "baz\n");
+    assert_eq!(read(dir.path().join("a.txt.bak")), "foo\n");
+    // Files left unchanged get no backup
+    assert!(!dir.path().join("b.txt.bak").exists());
+}
+
+#[test]
+fn dry_run_leaves_files_unchanged() {
+    let dir = TempDir::new().unwrap();
+    let paths = files(&dir, &[("a.txt", "foo\n")]);
+
+    let mut config = in_place("foo", "bar", paths.clone());
+    config.backup = true;
+    config.dry_run = true;
+    assert_eq!(execute_search(&config).unwrap(), ExitCode::Success);
+    assert_eq!(read(&paths[0]), "foo\n");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn missing_file_aborts_before_writing() {
+    let dir = TempDir::new().unwrap();
+    let mut paths = files(&dir, &[("a.txt", "foo\n")]);
+    paths.push(dir.path().join("missing.txt").to_string_lossy().to_string());
+
+    let error = execute_search(&in_place("foo", "bar", paths.clone())).unwrap_err();
+    assert!(matches!(error, RgrepError::IoError { ref path, .. } if *path == paths[1]));
+    assert_eq!(read(&paths[0]), "foo\n");
+}
+
+#[test]
+fn existing_backup_is_not_overwritten() {
+    let dir = TempDir::new().unwrap();
+    let paths = files(
+        &dir,
+        &[
+            ("a.txt", "foo 1\n"),
+            ("b.txt", "foo 2\n"),
+            ("b.txt.bak", "old\n"),
+        ],
+    );
+
+    let mut config = in_place("foo", "bar", paths[..2].to_vec());
+    config.backup = true;
+    let error = execute_search(&config).unwrap_err();
+    assert!(matches!(error, RgrepError::IoError { ref path, .. } if *path == paths[1]));
+    assert!(error.to_string().contains("already exists"));
+
+    // Nothing was written, not even the first file
+    assert_eq!(read(&paths[0]), "foo 1\n");
+    assert_eq!(read(&paths[1]), "foo 2\n");
+    assert_eq!(read(&paths[2]), "old\n");
+    assert!(!dir.path().join("a.txt.bak").exists());
+}
+
+#[test]
+fn file_named_twice_is_rewritten_once() {
+    let dir = TempDir::new().unwrap();
+    let mut paths = files(&dir, &[("a.txt", "foo\n")]);
+    let same = dir.path().join(".").join("a.txt");
+    paths.push(same.to_string_lossy().to_string());
+    #[cfg(unix)]
+    {
+        let link = dir.path().join("l.txt");
+        std::os::unix::fs::symlink(&paths[0], &link).unwrap();
+        paths.push(link.to_string_lossy().to_string());
+    }
+
+    let mut config = in_place("foo", "bar", paths.clone());
+    config.backup = true;
+    assert_eq!(execute_search(&config).unwrap(), ExitCode::Success);
+    assert_eq!(read(&paths[0]), "bar\n");
+    // The backup holds the original, not the first rewrite
+    assert_eq!(read(dir.path().join("a.txt.bak")), "foo\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn rewrite_more_files_than_open_file_limit() {
+    use std::process::Command;
+
+    let dir = TempDir::new().unwrap();
+    for index in 0..300 {
+        fs::write(dir.path().join(format!("{index}.txt")), "foo\n").unwrap();
+    }
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(r#"ulimit -n 64 && exec "$0" -r --replace bar --in-place foo "$1""#)
+        .arg(env!("CARGO_BIN_EXE_rgrep"))
+        .arg(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    for index in 0..300 {
+        assert_eq!(read(dir.path().join(format!("{index}.txt"))), "bar\n");
+    }
+}
@@ -1,91 +1,473 @@
+use std::path::Path;
+
 use rgrep::app;
-use rgrep::search::{create_matcher, format_match, search_lines, MatchResult};
-use rgrep::Config;
+use rgrep::search::{
+    create_matcher, format_match, format_only_matching, replace_matches, search_lines, MatchResult,
+    OutputOptions,
+};
+use rgrep::searcher::BufferSearcher;
+use rgrep::{BinaryFiles, Config, ExitCode, OutputMode, SortBy};
 
 #[test]
 fn search_literal_match() {
     let config = Config::new("test".into(), vec![], false, false);
-    let matcher = create_matcher(&config);
+    let matcher = create_matcher(&config).unwrap();
 
     let input = "line 1\nthis is a test\nline 3";
-    let lines = input.lines().map(|s| Ok(s.to_string()));
+    let lines = input.lines().map(|s| Ok(s.as_bytes().to_vec()));
 
     let results: Vec<_> = search_lines(lines, matcher.as_ref()).collect();
 
     let matched: Vec<_> = results.into_iter().filter(|r| r.matched).collect();
     assert_eq!(matched.len(), 1);
-    assert_eq!(matched[0].line, "this is a test");
+    assert_eq!(matched[0].line, b"this is a test");
     assert_eq!(matched[0].line_number, 2);
+    assert_eq!(matched[0].spans, vec![10..14]);
 }
 
 #[test]
 fn search_case_insensitive_match() {
     let config = Config::new("TEST".into(), vec![], true, false);
-    let matcher = create_matcher(&config);
+    let matcher = create_matcher(&config).unwrap();
 
     let input = "line 1\nthis is a test\nline 3";
-    let lines = input.lines().map(|s| Ok(s.to_string()));
+    let lines = input.lines().map(|s| Ok(s.as_bytes().to_vec()));
 
     let results: Vec<_> = search_lines(lines, matcher.as_ref()).collect();
 
     let matched: Vec<_> = results.into_iter().filter(|r| r.matched).collect();
     assert_eq!(matched.len(), 1);
-    assert_eq!(matched[0].line, "this is a test");
+    assert_eq!(matched[0].line, b"this is a test");
+}
+
+#[test]
+fn search_smart_case() {
+    let matches = |pattern: &str, regex: bool, line: &str| {
+        let mut config = Config::new(pattern.into(), vec![], false, false);
+        config.smart_case = true;
+        config.regex = regex;
+        create_matcher(&config).unwrap().matches(line.as_bytes())
+    };
+
+    assert!(matches("error", false, "ERROR: disk full"));
+    assert!(!matches("Error", false, "ERROR: disk full"));
+    assert!(matches("Error", false, "Error: disk full"));
+    assert!(matches(r"err\S+", true, "ERRORS"));
+    assert!(!matches(r"Err\S+", true, "ERRORS"));
+}
+
+#[test]
+fn search_multiple_patterns() {
+    let find_all = |patterns: &[&str], ignore_case: bool, regex: bool, line: &str| {
+        let mut config = Config::new(patterns[0].into(), vec![], ignore_case, false);
+        config.patterns = patterns[1..].iter().map(|p| p.to_string()).collect();
+        config.regex = regex;
+        let matcher = create_matcher(&config).unwrap();
+        matcher
+            .find_all(line.as_bytes())
+            .into_iter()
+            .map(|span| line[span].to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Literal patterns, preferring the longest match at a position
+    assert_eq!(
+        find_all(
+            &["foo", "bar", "foobar"],
+            false,
+            false,
+            "a foobar, a bar, a FOO"
+        ),
+        ["foobar", "bar"]
+    );
+    assert_eq!(
+        find_all(&["foo", "bar"], true, false, "a FOO and a Bar"),
+        ["FOO", "Bar"]
+    );
+    assert_eq!(
+        find_all(&["straße", "bar"], true, false, "STRASSE, BAR"),
+        ["STRASSE", "BAR"]
+    );
+    assert_eq!(
+        find_all(&[r"\d+", "x$"], false, true, "a1 b22 x"),
+        ["1", "22", "x"]
+    );
+
+    // Each regex must be valid on its own
+    let mut config = Config::new("a)|(b".into(), vec![], false, false);
+    config.patterns = vec!["c".to_string()];
+    config.regex = true;
+    assert!(create_matcher(&config).is_err());
+}
+
+#[test]
+fn search_word_and_line_regexp() {
+    let find_all = |pattern: &str, ignore_case: bool, regex: bool, word: bool, line: &str| {
+        let mut config = Config::new(pattern.into(), vec![], ignore_case, false);
+        config.regex = regex;
+        config.word_regexp = word;
+        config.line_regexp = !word;
+        let matcher = create_matcher(&config).unwrap();
+        matcher
+            .find_all(line.as_bytes())
+            .into_iter()
+            .map(|span| line[span].to_string())
+            .collect::<Vec<_>>()
+    };
+    let words =
+        |pattern, ignore_case, regex, line| find_all(pattern, ignore_case, regex, true, line);
+    let lines =
+        |pattern, ignore_case, regex, line| find_all(pattern, ignore_case, regex, false, line);
+
+    // A rejected occurrence inside a word does not hide a later whole word
+    assert_eq!(words("id", false, false, "width valid id_x id"), ["id"]);
+    assert!(words("id", false, false, "width valid").is_empty());
+    assert_eq!(words("ID", true, false, "valid Id, (id)"), ["Id", "id"]);
+    assert_eq!(words("über", true, false, "Übermut ÜBER"), ["ÜBER"]);
+    assert_eq!(words(r"\d+", false, true, "a1 22 b3c 4"), ["22", "4"]);
+    // A longer alternative is tried when a shorter one ends inside a word
+    assert_eq!(words("foo|foobar", false, true, "foobar"), ["foobar"]);
+    assert_eq!(words("ab|a", false, true, "abc a"), ["a"]);
+
+    // The same for several literal patterns
+    let literal_words = |patterns: &[&str], ignore_case: bool, line: &str| {
+        let mut config = Config::new(patterns[0].into(), vec![], ignore_case, false);
+        config.patterns = patterns[1..].iter().map(|p| p.to_string()).collect();
+        config.word_regexp = true;
+        let matcher = create_matcher(&config).unwrap();
+        matcher
+            .find_all(line.as_bytes())
+            .into_iter()
+            .map(|span| line[span].to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        literal_words(&["foo bar", "foo"], false, "foo barx"),
+        ["foo"]
+    );
+    assert_eq!(literal_words(&["a b", "a"], false, "a bc"), ["a"]);
+    assert_eq!(
+        literal_words(&["STRASSE", "str"], true, "Straße"),
+        ["Straße"]
+    );
+    assert_eq!(literal_words(&["ab", "a"], true, "abc ab"), ["ab"]);
+
+    assert_eq!(lines("foo", false, false, "foo"), ["foo"]);
+    assert!(lines("foo", false, false, "foo bar").is_empty());
+    assert_eq!(lines("FOO", true, false, "foo"), ["foo"]);
+    // The whole line is preferred over a shorter alternative
+    assert_eq!(lines("a|ab", false, true, "ab"), ["ab"]);
+    assert!(lines(r"\d+", false, true, "12a").is_empty());
+}
+
+#[test]
+fn search_many_literal_patterns() {
+    let blocklist: Vec<String> = (0..5000).map(|n| format!("token{n:04}x")).collect();
+    let mut config = Config::new("never-seen".into(), vec![], false, false);
+    config.patterns = blocklist;
+    let matcher = create_matcher(&config).unwrap();
+
+    assert!(matcher.matches(b"leaked token4999x here"));
+    assert!(matcher.matches(b"token0000x"));
+    assert!(!matcher.matches(b"token5000x token123x"));
+}
+
+#[test]
+fn search_many_case_insensitive_patterns_in_unicode_text() {
+    let blocklist: Vec<String> = (0..3000).map(|n| format!("token{n:04}x")).collect();
+    let mut config = Config::new("never-seen".into(), vec![], true, false);
+    config.patterns = blocklist;
+    let matcher = create_matcher(&config).unwrap();
+
+    // Every line has a non-ASCII character, so the text is folded; this
+    // takes minutes if each position is tried with every pattern
+    let mut input = String::new();
+    for n in 0..50_000 {
+        input.push_str(&format!("café au lait numéro {n} sans tokens\n"));
+        if n % 10_000 == 9_999 {
+            input.push_str("une TO\u{212A}EN2999X ici\n");
+        }
+    }
+
+    let started = std::time::Instant::now();
+    let matched: Vec<_> = BufferSearcher::new(input.as_bytes(), matcher.as_ref()).collect();
+    let elapsed = started.elapsed();
+
+    assert_eq!(matched.len(), 5);
+    assert_eq!(matched[0].spans, vec![4..16]);
+    assert!(elapsed.as_secs() < 10, "searching took {elapsed:?}");
+}
+
+/// Case-insensitive pattern, line, and the text it should match in the line
+const CASE_FOLDING_CORPUS: &[(&str, &str, Option<&str>)] = &[
+    // Latin
+    ("straße", "STRASSE", Some("STRASSE")),
+    ("STRASSE", "Die Straße", Some("Straße")),
+    ("ẞ", "Fuß", Some("ß")),
+    ("KELVIN", "300 \u{212A}elvin", Some("\u{212A}elvin")),
+    ("ÉCOLE", "une école", Some("école")),
+    ("file", "ﬁle", Some("ﬁle")),
+    ("fi", "ﬁle", Some("ﬁ")),
+    ("f", "ﬁ", None),
+    ("i", "ı", None),
+    // Greek, including final sigma and iota subscript
+    ("ΣΊΣΥΦΟΣ", "ο σίσυφος", Some("σίσυφος")),
+    ("ᾼ", "ᾳ", Some("ᾳ")),
+    ("μ", "5 µm", Some("µ")),
+    // Cyrillic
+    ("ПРИВЕТ", "привет, мир", Some("привет")),
+    ("Ё", "ёлка", Some("ё")),
+    // Armenian ligature
+    ("եւ", "և", Some("և")),
+    // Georgian Mtavruli and Cherokee
+    ("ᲐᲑᲒ", "აბგ", Some("აბგ")),
+    ("ᏣᎳᎩ", "ꮳꮃꭹ", Some("ꮳꮃꭹ")),
+    // Deseret, outside the Basic Multilingual Plane
+    ("𐐀𐐁", "𐐨𐐩", Some("𐐨𐐩")),
+    // Scripts without case are matched exactly
+    ("日本", "日本語", Some("日本")),
+    ("مرحبا", "مرحبا بالعالم", Some("مرحبا")),
+];
+
+#[test]
+fn search_case_insensitive_unicode_corpus() {
+    // A second pattern switches to the matchers for several literals
+    for extra in [None, Some("qqqq")] {
+        for &(pattern, line, expected) in CASE_FOLDING_CORPUS {
+            let mut config = Config::new(pattern.into(), vec![], true, false);
+            config.patterns.extend(extra.map(String::from));
+            let matcher = create_matcher(&config).unwrap();
+
+            let found = matcher
+                .find(line.as_bytes())
+                .map(|span| &line.as_bytes()[span]);
+            assert_eq!(
+                found,
+                expected.map(str::as_bytes),
+                "pattern {pattern:?} (and {extra:?}) in {line:?}"
+            );
+        }
+    }
+
+    // Smart case ignores case the same way
+    let mut config = Config::new("kelvin".into(), vec![], false, false);
+    config.patterns = vec!["strasse".into()];
+    config.smart_case = true;
+    let matcher = create_matcher(&config).unwrap();
+    assert!(matcher.matches("300 \u{212A}elvin".as_bytes()));
+    assert!(matcher.matches("Die Straße".as_bytes()));
+    assert!(matcher.matches(b"KELVIN"));
+}
+
+#[test]
+fn search_regex_match() {
+    let mut config = Config::new(r"^line \d$".into(), vec![], false, false);
+    config.regex = true;
+    let matcher = create_matcher(&config).unwrap();
+
+    let input = "line 1\nthis is a test\nline 3";
+    let lines = input.lines().map(|s| Ok(s.as_bytes().to_vec()));
+
+    let results: Vec<_> = search_lines(lines, matcher.as_ref()).collect();
+
+    let matched: Vec<_> = results.into_iter().filter(|r| r.matched).collect();
+    assert_eq!(matched.len(), 2);
+    assert_eq!(matched[0].line_number, 1);
+    assert_eq!(matched[1].line_number, 3);
+}
+
+#[test]
+fn search_inverted_selection() {
+    let config = Config::new("test".into(), vec![], false, false);
+    let matcher = create_matcher(&config).unwrap();
+
+    let input = "line 1\nthis is a test\nline 3";
+    let lines = input.lines().map(|s| Ok(s.as_bytes().to_vec()));
+
+    let selected: Vec<_> = search_lines(lines, matcher.as_ref())
+        .filter(|r| r.is_selected(true))
+        .collect();
+    assert_eq!(selected.len(), 2);
+    assert_eq!(selected[0].line_number, 1);
+    assert_eq!(selected[1].line_number, 3);
+    assert!(selected.iter().all(|r| r.spans.is_empty()));
+}
+
+#[test]
+fn create_matcher_rejects_invalid_regex() {
+    let mut config = Config::new("[unclosed".into(), vec![], false, false);
+    config.regex = true;
+
+    let error = create_matcher(&config).err().unwrap();
+    assert_eq!(error.exit_code(), ExitCode::InvalidArgs);
+}
+
+#[test]
+fn format_only_matching_extracts_every_match() {
+    let mut config = Config::new(r"ERR-\d+".into(), vec![], false, true);
+    config.regex = true;
+    config.only_matching = true;
+    let matcher = create_matcher(&config).unwrap();
+    let options = OutputOptions {
+        show_line_numbers: true,
+        ..OutputOptions::default()
+    };
+
+    let input = "ok\nERR-1 then ERR-22\nERR-3\n";
+    let parts: Vec<_> = BufferSearcher::new(input.as_bytes(), matcher.as_ref())
+        .flat_map(|m| format_only_matching(&m, None, &options))
+        .map(|part| String::from_utf8(part).unwrap())
+        .collect();
+
+    assert_eq!(parts, ["2:ERR-1", "2:ERR-22", "3:ERR-3"]);
+}
+
+#[test]
+fn replace_matches_expands_captures() {
+    let replace = |pattern: &str, regex: bool, word: bool, replacement: &str, line: &str| {
+        let mut config = Config::new(pattern.into(), vec![], false, false);
+        config.regex = regex;
+        config.word_regexp = word;
+        let matcher = create_matcher(&config).unwrap();
+        let line = line.as_bytes().to_vec();
+        let match_result = MatchResult {
+            spans: matcher.find_all(&line),
+            line,
+            line_number: 1,
+            byte_offset: 0,
+            matched: true,
+        };
+        let replaced = replace_matches(match_result, matcher.as_ref(), replacement.as_bytes());
+        let parts: Vec<_> = replaced
+            .spans
+            .iter()
+            .map(|span| String::from_utf8(replaced.line[span.clone()].to_vec()).unwrap())
+            .collect();
+        (String::from_utf8(replaced.line).unwrap(), parts)
+    };
+
+    assert_eq!(
+        replace("foo", false, false, "bar", "foo, foo"),
+        (
+            "bar, bar".to_string(),
+            vec!["bar".to_string(), "bar".to_string()]
+        )
+    );
+    // References are only expanded for regexes
+    assert_eq!(replace("foo", false, false, "$1", "a foo").0, "a $1");
+    assert_eq!(
+        replace(r"(\w+)_id", true, false, "${1}Id", "user_id = other_id").0,
+        "userId = otherId"
+    );
+    assert_eq!(
+        replace(
+            r"(?P<key>\w+)=(?P<value>\d+)",
+            true,
+            false,
+            "$value:$key",
+            "a=1 b=22"
+        )
+        .0,
+        "1:a 22:b"
+    );
+    assert_eq!(
+        replace(r"(\w)d", true, true, "<$1>", "id width ad").0,
+        "<i> width <a>"
+    );
+    // An empty replacement removes the match
+    assert_eq!(replace("o", false, false, "", "foo bar").0, "f bar");
+}
+
+#[test]
+fn replace_matches_with_several_regexes() {
+    let replace = |patterns: &[&str], replacement: &str, line: &str| {
+        let mut config = Config::new(patterns[0].into(), vec![], false, false);
+        config.patterns = patterns[1..].iter().map(|p| p.to_string()).collect();
+        config.regex = true;
+        let matcher = create_matcher(&config).unwrap();
+        let line = line.as_bytes().to_vec();
+        let match_result = MatchResult {
+            spans: matcher.find_all(&line),
+            line,
+            line_number: 1,
+            byte_offset: 0,
+            matched: true,
+        };
+        let replaced = replace_matches(match_result, matcher.as_ref(), replacement.as_bytes());
+        String::from_utf8(replaced.line).unwrap()
+    };
+
+    // Group numbers and names refer to the pattern that matched
+    assert_eq!(replace(&["(a)", "(b)"], "<$1>", "a b"), "<a> <b>");
+    assert_eq!(
+        replace(&[r"(?P<k>\w)=\d", r"(?P<k>\w):\d"], "$k", "x=1 y:2"),
+        "x y"
+    );
+    // The first pattern matching at a position wins, as when searching
+    assert_eq!(replace(&["(a)(b)", "a(b)"], "$2", "ab"), "b");
 }
 
 #[test]
 fn format_match_basic() {
     let match_result = MatchResult {
-        line: "hello world".to_string(),
+        line: b"hello world".to_vec(),
         line_number: 5,
+        byte_offset: 0,
         matched: true,
+        spans: Vec::new(),
     };
 
     assert_eq!(
         format_match(&match_result, None, false, false),
-        "hello world"
+        b"hello world"
     );
 }
 
 #[test]
 fn format_match_with_line_numbers() {
     let match_result = MatchResult {
-        line: "hello world".to_string(),
+        line: b"hello world".to_vec(),
         line_number: 5,
+        byte_offset: 0,
         matched: true,
+        spans: Vec::new(),
     };
 
     assert_eq!(
         format_match(&match_result, None, false, true),
-        "5:hello world"
+        b"5:hello world"
     );
 }
 
 #[test]
 fn format_match_with_filename() {
     let match_result = MatchResult {
-        line: "hello world".to_string(),
+        line: b"hello world".to_vec(),
         line_number: 5,
+        byte_offset: 0,
         matched: true,
+        spans: Vec::new(),
     };
 
     assert_eq!(
-        format_match(&match_result, Some("test.txt"), true, false),
-        "test.txt:hello world"
+        format_match(&match_result, Some(Path::new("test.txt")), true, false),
+        b"test.txt:hello world"
     );
 }
 
 #[test]
 fn format_match_with_filename_and_line_numbers() {
     let match_result = MatchResult {
-        line: "hello world".to_string(),
+        line: b"hello world".to_vec(),
         line_number: 5,
+        byte_offset: 0,
         matched: true,
+        spans: Vec::new(),
     };
 
     assert_eq!(
-        format_match(&match_result, Some("test.txt"), true, true),
-        "test.txt:5:hello world"
+        format_match(&match_result, Some(Path::new("test.txt")), true, true),
+        b"test.txt:5:hello world"
     );
 }
 
@@ -116,4 +498,143 @@ mod integration {
         let result = super::app::execute_search(&config);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn app_summary_modes_exit_codes() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "foo").unwrap();
+        temp_file.flush().unwrap();
+
+        let path = temp_file.path().to_string_lossy().to_string();
+
+        for mode in [
+            OutputMode::Count,
+            OutputMode::FilesWithMatches,
+            OutputMode::FilesWithoutMatch,
+        ] {
+            let mut config = Config::new("foo".to_string(), vec![path.clone()], false, false);
+            config.output_mode = mode;
+            assert_eq!(app::execute_search(&config).unwrap(), ExitCode::Success);
+
+            let mut config = Config::new("bar".to_string(), vec![path.clone()], false, false);
+            config.output_mode = mode;
+            assert_eq!(app::execute_search(&config).unwrap(), ExitCode::NoMatches);
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_lines_are_searched() {
+        // Latin-1 encoded "café foo" followed by a stray continuation byte
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .write_all(b"caf\xe9 foo\r\n\x80 bar\nfoo\n")
+            .unwrap();
+        temp_file.flush().unwrap();
+
+        let path = temp_file.path().to_string_lossy().to_string();
+        let input = rgrep::io::create_input_source(Some(&path)).unwrap();
+        assert!(!input.binary);
+
+        let config = Config::new("foo".to_string(), vec![path.clone()], false, false);
+        let matcher = create_matcher(&config).unwrap();
+        let matched: Vec<_> = BufferSearcher::new(input.reader, matcher.as_ref()).collect();
+
+        assert_eq!(matched.len(), 2);
+        assert_eq!(matched[0].line, b"caf\xe9 foo");
+        assert_eq!(matched[0].spans, vec![5..8]);
+        assert_eq!(matched[1].line_number, 3);
+
+        let config = Config::new("bar".to_string(), vec![path], false, false);
+        assert_eq!(app::execute_search(&config).unwrap(), ExitCode::Success);
+    }
+
+    #[test]
+    fn app_parallel_exit_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = Vec::new();
+        for i in 0..8 {
+            let path = dir.path().join(format!("f{i}.txt"));
+            std::fs::write(&path, if i == 5 { "foo\n" } else { "bar\n" }).unwrap();
+            files.push(path.to_string_lossy().to_string());
+        }
+        files.push(dir.path().join("missing.txt").to_string_lossy().to_string());
+
+        let modes = [OutputMode::FilesWithMatches, OutputMode::Quiet];
+        for (threads, output_mode) in [1, 4].into_iter().flat_map(|t| modes.map(|m| (t, m))) {
+            for sort in [SortBy::None, SortBy::Path] {
+                let mut config = Config::new("foo".to_string(), files.clone(), false, false);
+                config.threads = Some(threads);
+                config.sort = sort;
+                config.output_mode = output_mode;
+                config.max_count = Some(1);
+                assert_eq!(app::execute_search(&config).unwrap(), ExitCode::Success);
+
+                config.pattern = "baz".to_string();
+                assert_eq!(app::execute_search(&config).unwrap(), ExitCode::NoMatches);
+            }
+        }
+    }
+
+    #[test]
+    fn app_binary_files_policy() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"foo\0bar\nfoo\n").unwrap();
+        temp_file.flush().unwrap();
+
+        let path = temp_file.path().to_string_lossy().to_string();
+        let input = rgrep::io::create_input_source(Some(&path)).unwrap();
+        assert!(input.binary);
+
+        for (policy, expected) in [
+            (BinaryFiles::Binary, ExitCode::Success),
+            (BinaryFiles::Text, ExitCode::Success),
+            (BinaryFiles::WithoutMatch, ExitCode::NoMatches),
+        ] {
+            let mut config = Config::new("foo".to_string(), vec![path.clone()], false, false);
+            config.binary_files = policy;
+            assert_eq!(app::execute_search(&config).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn app_inverted_exit_codes() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "foo").unwrap();
+        writeln!(temp_file, "foo again").unwrap();
+        temp_file.flush().unwrap();
+
+        let path = temp_file.path().to_string_lossy().to_string();
+
+        // Every line matches, so nothing is selected when inverted
+        let mut config = Config::new("foo".to_string(), vec![path.clone()], false, false);
+        config.invert_match = true;
+        assert_eq!(app::execute_search(&config).unwrap(), ExitCode::NoMatches);
+
+        // No line matches, so every line is selected when inverted
+        let mut config = Config::new("bar".to_string(), vec![path], false, false);
+        config.invert_match = true;
+        assert_eq!(app::execute_search(&config).unwrap(), ExitCode::Success);
+    }
+
+    #[test]
+    fn app_without_patterns_matches_nothing() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "foo").unwrap();
+        temp_file.flush().unwrap();
+
+        let path = temp_file.path().to_string_lossy().to_string();
+
+        // As with `-f /dev/null`
+        for (regex, invert_match, expected) in [
+            (false, false, ExitCode::NoMatches),
+            (true, false, ExitCode::NoMatches),
+            (false, true, ExitCode::Success),
+        ] {
+            let mut config = Config::new(String::new(), vec![path.clone()], false, false);
+            config.no_patterns = true;
+            config.regex = regex;
+            config.invert_match = invert_match;
+            assert_eq!(app::execute_search(&config).unwrap(), expected);
+        }
+    }
 }
@@ -0,0 +1,275 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::run_config;
+use rgrep::walk::Walk;
+use tempfile::TempDir;
+
+fn tree() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("a/b")).unwrap();
+    fs::create_dir_all(dir.path().join("c")).unwrap();
+    fs::write(dir.path().join("a/b/x.txt"), "hit\n").unwrap();
+    fs::write(dir.path().join("a/w.txt"), "miss\n").unwrap();
+    fs::write(dir.path().join("c/y.txt"), "hit\n").unwrap();
+    fs::write(dir.path().join("z.txt"), "miss\n").unwrap();
+    dir
+}
+
+fn relative(dir: &TempDir, paths: Vec<PathBuf>) -> Vec<String> {
+    paths
+        .into_iter()
+        .map(|p| {
+            p.strip_prefix(dir.path())
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn walk_visits_files_in_name_order() {
+    let dir = tree();
+    let files: Vec<_> = Walk::new(dir.path(), false).map(Result::unwrap).collect();
+
+    assert_eq!(
+        relative(&dir, files),
+        vec!["a/b/x.txt", "a/w.txt", "c/y.txt", "z.txt"]
+    );
+}
+
+#[test]
+fn walk_single_file_root() {
+    let dir = tree();
+    let files: Vec<_> = Walk::new(dir.path().join("z.txt"), false)
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(relative(&dir, files), vec!["z.txt"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn walk_symlinks_only_followed_when_requested() {
+    let dir = tree();
+    std::os::unix::fs::symlink(dir.path().join("c"), dir.path().join("a/link")).unwrap();
+
+    let files: Vec<_> = Walk::new(dir.path().join("a"), false)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(relative(&dir, files), vec!["a/b/x.txt", "a/w.txt"]);
+
+    let files: Vec<_> = Walk::new(dir.path().join("a"), true)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        relative(&dir, files),
+        vec!["a/b/x.txt", "a/link/y.txt", "a/w.txt"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn walk_detects_symlink_loops() {
+    let dir = tree();
+    std::os::unix::fs::symlink(dir.path(), dir.path().join("a/b/up")).unwrap();
+
+    let results: Vec<_> = Walk::new(dir.path(), true).collect();
+    let errors: Vec<_> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().contains("recursive directory loop"));
+    assert_eq!(results.len() - errors.len(), 4);
+}
+
+#[test]
+fn filters_apply_to_command_line_files() {
+    use rgrep::{Config, ExitCode, OutputMode};
+
+    let dir = tree();
+    let file = dir.path().join("a/b/x.txt").to_string_lossy().to_string();
+    let root = dir.path().to_string_lossy().to_string();
+
+    for recursive in [false, true] {
+        let search = |files: Vec<String>, configure: fn(&mut Config)| {
+            let mut config = Config::new("hit".into(), files, false, false);
+            config.recursive = recursive;
+            config.output_mode = OutputMode::Quiet;
+            configure(&mut config);
+            rgrep::app::execute_search(&config).unwrap()
+        };
+
+        assert_eq!(
+            search(vec![file.clone()], |c| c.exclude = vec!["*.txt".into()]),
+            ExitCode::NoMatches
+        );
+        assert_eq!(
+            search(vec![file.clone()], |c| c.include = vec!["*.md".into()]),
+            ExitCode::NoMatches
+        );
+        assert_eq!(
+            search(vec![file.clone()], |c| c.include = vec!["x.*".into()]),
+            ExitCode::Success
+        );
+    }
+
+    // Directories given are still walked
+    let mut config = Config::new("hit".into(), vec![root], false, false);
+    config.recursive = true;
+    config.output_mode = OutputMode::Quiet;
+    config.include = vec!["*.txt".into()];
+    assert_eq!(
+        rgrep::app::execute_search(&config).unwrap(),
+        ExitCode::Success
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn recursive_search_opens_non_utf8_names() {
+    use rgrep::{Config, ExitCode, OutputMode};
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join(OsStr::from_bytes(b"bad\xffname.txt")),
+        "hit\n",
+    )
+    .unwrap();
+
+    for threads in [1, 2] {
+        let root = dir.path().to_string_lossy().to_string();
+        let mut config = Config::new("hit".into(), vec![root], false, false);
+        config.recursive = true;
+        config.output_mode = OutputMode::Quiet;
+        config.threads = Some(threads);
+        assert_eq!(
+            rgrep::app::execute_search(&config).unwrap(),
+            ExitCode::Success
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn recursive_search_prints_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Command;
+
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join(OsStr::from_bytes(b"n\xff.txt")), "hit\n").unwrap();
+
+    let output = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_rgrep"))
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        output.stdout
+    };
+
+    // The name is printed as the file system has it, not lossily
+    assert_eq!(output(&["-r", "hit"]), b"n\xff.txt:hit\n");
+    assert_eq!(output(&["-rl", "hit"]), b"n\xff.txt\n");
+    assert_eq!(output(&["-rc", "hit"]), b"n\xff.txt:1\n");
+
+    // So `-Z` lists can be passed on to `xargs -0`
+    assert_eq!(output(&["-rlZ", "hit"]), b"n\xff.txt\0");
+    assert_eq!(output(&["-rZ", "hit"]), b"n\xff.txt\0hit\n");
+    assert_eq!(output(&["-rcZ", "hit"]), b"n\xff.txt\x001\n");
+}
+
+#[test]
+fn parse_recursive_flags() {
+    let cfg = run_config(&["-r", "foo"]);
+    assert!(cfg.recursive);
+    assert!(!cfg.follow_links);
+    assert!(!cfg.no_ignore);
+    assert!(!cfg.hidden);
+    assert!(!cfg.use_stdin());
+    assert_eq!(cfg.actual_files(), vec!["."]);
+
+    let cfg = run_config(&["-R", "--no-ignore", "--hidden", "foo", "src"]);
+    assert!(cfg.recursive);
+    assert!(cfg.follow_links);
+    assert!(cfg.no_ignore);
+    assert!(cfg.hidden);
+    assert_eq!(cfg.actual_files(), vec!["src"]);
+}
+
+fn collect(walk: Walk, dir: &TempDir) -> Vec<String> {
+    relative(dir, walk.map(Result::unwrap).collect())
+}
+
+#[test]
+fn walk_respects_nested_ignore_files() {
+    let dir = tree();
+    fs::write(dir.path().join(".gitignore"), "*.txt\n!x.txt\n").unwrap();
+    fs::write(dir.path().join("a/.ignore"), "b/\n").unwrap();
+    fs::write(dir.path().join("c/.rgrepignore"), "/y.txt\n").unwrap();
+    fs::write(dir.path().join("c/keep.md"), "hit\n").unwrap();
+
+    assert_eq!(
+        collect(Walk::new(dir.path(), false), &dir),
+        vec!["c/keep.md"]
+    );
+
+    // Deeper ignore files override shallower ones
+    fs::write(dir.path().join("a/.ignore"), "!w.txt\n").unwrap();
+    assert_eq!(
+        collect(Walk::new(dir.path(), false), &dir),
+        vec!["a/b/x.txt", "a/w.txt", "c/keep.md"]
+    );
+}
+
+#[test]
+fn walk_skips_hidden_entries_unless_requested() {
+    let dir = tree();
+    fs::create_dir(dir.path().join(".cache")).unwrap();
+    fs::write(dir.path().join(".cache/data"), "hit\n").unwrap();
+    fs::write(dir.path().join(".env"), "hit\n").unwrap();
+
+    assert_eq!(collect(Walk::new(dir.path(), false), &dir).len(), 4);
+
+    let files = collect(Walk::new(dir.path(), false).hidden(true), &dir);
+    assert!(files.contains(&".cache/data".to_string()));
+    assert!(files.contains(&".env".to_string()));
+}
+
+#[test]
+fn walk_no_ignore_disables_ignore_files() {
+    let dir = tree();
+    fs::write(dir.path().join(".ignore"), "a/\n").unwrap();
+
+    assert_eq!(
+        collect(Walk::new(dir.path(), false), &dir),
+        vec!["c/y.txt", "z.txt"]
+    );
+    assert_eq!(
+        collect(Walk::new(dir.path(), false).respect_ignore(false), &dir),
+        vec!["a/b/x.txt", "a/w.txt", "c/y.txt", "z.txt"]
+    );
+}
+
+#[test]
+fn walk_uses_repository_ignores_above_the_root() {
+    let dir = tree();
+    fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+    fs::write(dir.path().join(".git/info/exclude"), "x.txt\n").unwrap();
+    fs::write(dir.path().join(".gitignore"), "/a/w.txt\n").unwrap();
+
+    assert_eq!(
+        collect(Walk::new(dir.path().join("a"), false), &dir),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        collect(Walk::new(dir.path(), false), &dir),
+        vec!["c/y.txt", "z.txt"]
+    );
+}